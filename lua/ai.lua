@@ -0,0 +1,21 @@
+-- AI sensors and actions for the planner. Use them in data/actions.toml by name, like the
+-- built-in ones.
+
+ai.sensor("TargetFar", function(me, target)
+  if target == nil then
+    return false
+  end
+
+  local x1, _, z1 = world.position(me)
+  local x2, _, z2 = world.position(target)
+  if x1 == nil or x2 == nil then
+    return false
+  end
+
+  return (x2 - x1) ^ 2 + (z2 - z1) ^ 2 > 12 * 12
+end)
+
+ai.action("Wander", function(me, target)
+  local dirs = { "N", "NE", "E", "SE", "S", "SW", "W", "NW" }
+  return ai.go(dirs[rand.between(1, 9)])
+end)
@@ -0,0 +1,26 @@
+-- Sends another dood after the player every so often, as long as there aren't too many around.
+
+local WAVE_TICKS = 60 * 30
+local MAX_DOODS = 6
+
+local function count_doods()
+  local count = 0
+  for _, e in ipairs(world.entities()) do
+    if world.name(e) == "Dood" and e ~= world.player() then
+      count = count + 1
+    end
+  end
+  return count
+end
+
+on("tick", function(frame)
+  if frame == 0 or frame % WAVE_TICKS ~= 0 or count_doods() >= MAX_DOODS then
+    return
+  end
+
+  local w, h = world.size()
+  local x = rand.between(1, w - 1)
+  local z = rand.between(1, h - 1)
+  world.spawn("dood", x, z)
+  log.info("A dood appears!")
+end)
@@ -0,0 +1,73 @@
+-- Loaded before any other script.
+
+hooks = {}
+
+-- Calls `f` every time the game runs the hook `name`. The game runs "start" when a new world is
+-- made and "tick" with the frame number on every simulation tick.
+function on(name, f)
+  hooks[name] = hooks[name] or {}
+  table.insert(hooks[name], f)
+  register_hook(name)
+end
+
+function run_hooks(name, ...)
+  for _, f in ipairs(hooks[name] or {}) do
+    f(...)
+  end
+end
+
+-- AI sensors and actions. See src/lua/ai.rs.
+
+ai = {}
+local sensors = {}
+local actions = {}
+
+function ai.sensor(name, f)
+  sensors[name] = f
+  ai_register_sensor(name)
+end
+
+function ai.action(name, f)
+  actions[name] = f
+  ai_register_action(name)
+end
+
+function ai.go(dir)
+  return "go " .. dir
+end
+
+function ai.shoot(angle)
+  return "shoot " .. angle
+end
+
+function ai.melee(angle)
+  return "melee " .. angle
+end
+
+function ai.reload()
+  return "reload"
+end
+
+function ai.wait()
+  return "wait"
+end
+
+-- Runs every sensor in `names` for every `{ me, target }` in `calls`. The results come back as one
+-- string of "1"s and "0"s, all the sensors for the first call, then for the second, and so on.
+function ai_sense_all(names, calls)
+  local results = {}
+  for _, call in ipairs(calls) do
+    for _, name in ipairs(names) do
+      local ok, result = pcall(sensors[name], call[1], call[2])
+      if not ok then
+        log.error("Error in Lua sensor " .. name .. ": " .. tostring(result))
+      end
+      table.insert(results, (ok and result == true) and "1" or "0")
+    end
+  end
+  return table.concat(results)
+end
+
+function ai_act(name, me, target)
+  return actions[name](me, target)
+end
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use calx_ecs::Entity;
 use goap::*;
 
@@ -10,16 +12,33 @@ use rand::{self, Rng};
 use world::{self, World};
 
 use ai;
+use lua;
 use super::{Ai, AiProp, AiGoal, Target};
 
 macro_rules! generate_ai_actions {
     ( $( $action:ident, $func:ident );+ $(;)*) => {
-        macro_attr! {
-        #[derive(Serialize, Deserialize, Hash, Ord, PartialOrd, Eq, PartialEq, Debug, Clone, EnumFromStr!)]
-            pub enum AiAction {
-                $(
-                    $action,
-                )*
+        #[derive(Serialize, Deserialize, Hash, Ord, PartialOrd, Eq, PartialEq, Debug, Clone)]
+        pub enum AiAction {
+            $(
+                $action,
+            )*
+
+            /// Handled by a Lua function registered with `ai.action`.
+            Script(String),
+        }
+
+        impl FromStr for AiAction {
+            type Err = ();
+
+            /// Names that aren't built in are taken to be Lua actions. Whether a script
+            /// actually defines them is checked when the planner loads.
+            fn from_str(s: &str) -> Result<AiAction, ()> {
+                match s {
+                    $(
+                        stringify!($action) => Ok(AiAction::$action),
+                    )*
+                    _ => Ok(AiAction::Script(s.to_string())),
+                }
             }
         }
 
@@ -34,6 +53,7 @@ macro_rules! generate_ai_actions {
                         $(
                             AiAction::$action => $func(entity, world),
                         )*
+                        AiAction::Script(ref name) => lua::ai::act(name, entity, ai::target_entity(ai)),
                     }
                 },
                 None => {
@@ -56,7 +76,10 @@ macro_rules! generate_ai_actions {
 generate_ai_actions! {
     Wait, ai_wait;
     MoveCloser, ai_move_closer;
+    MoveIntoMelee, ai_move_closer;
     ShootAt, ai_shoot_at;
+    Reload, ai_reload;
+    SwingAt, ai_swing_at;
     RunAway, ai_run_away;
 }
 
@@ -120,17 +143,20 @@ fn ai_move_closer(entity: Entity, world: &World) -> Action {
 //     Action::Pickup(target)
 // }
 // 
-// fn ai_swing_at(entity: Entity, world: &World) -> Action {
-//     let ai = &world.ecs().ais.get_or_err(entity).data;
-// 
-//     Action::SwingAt(ai.targets.borrow().peek().unwrap().entity.unwrap())
-// }
-// 
 fn ai_shoot_at(entity: Entity, world: &World) -> Action {
     let angle = angle_towards_target(entity, world);
     Action::Shoot(angle)
 }
 
+fn ai_swing_at(entity: Entity, world: &World) -> Action {
+    let angle = angle_towards_target(entity, world);
+    Action::Melee(angle)
+}
+
+fn ai_reload(_entity: Entity, _world: &World) -> Action {
+    Action::Reload
+}
+
 fn ai_run_away(entity: Entity, world: &World) -> Action {
     match direction_towards_target(entity, world) {
         Some(dir) => Action::Go(dir.reverse()),
@@ -138,15 +164,20 @@ fn ai_run_away(entity: Entity, world: &World) -> Action {
     }
 }
 
-fn angle_towards_target(entity: Entity, world: &World) -> f32 {
-    let ais = &world.ecs().ais;
-    let ai = &ais.get_or_err(entity).data;
+/// Where the AI's current target is, falling back to the player if it has none.
+fn target_position(entity: Entity, world: &World) -> Option<Point> {
+    let ai = &world.ecs().ais.get_or_err(entity).data;
 
-    let target = world.player().unwrap();
-    let target_pos = world.position(target).unwrap().pos;
-    let my_pos = world.position(entity).unwrap().pos;
+    let target = ai.targets.borrow().peek().and_then(|t| t.position(world));
+    target.or_else(|| world.player().and_then(|p| world.position(p)).map(|p| p.pos))
+}
 
-    point::angle_3f(my_pos, target_pos)
+fn angle_towards_target(entity: Entity, world: &World) -> f32 {
+    let my_pos = world.position(entity).unwrap().pos;
+    match target_position(entity, world) {
+        Some(target_pos) => point::angle_3f(my_pos, target_pos),
+        None => world.position(entity).unwrap().dir,
+    }
 }
 
 fn direction_towards(entity: Entity, target_pos: Point, world: &World) -> Option<Direction> {
@@ -177,12 +208,7 @@ fn direction_towards(entity: Entity, target_pos: Point, world: &World) -> Option
 }
 
 fn direction_towards_target(entity: Entity, world: &World) -> Option<Direction> {
-    let ais = &world.ecs().ais;
-    let ai = &ais.get_or_err(entity).data;
-
-    let target = world.player().unwrap();
-    let target_pos = world.position(target).unwrap().pos;
-    direction_towards(entity, target_pos, world)
+    target_position(entity, world).and_then(|target_pos| direction_towards(entity, target_pos, world))
 }
 
 fn warn_of_unreachable_states(entity: Entity, world: &World, ai: &Ai) {
@@ -1,15 +1,19 @@
+use alga::linear::EuclideanSpace;
 use calx_ecs::Entity;
 
 use ai::*;
+use ecs::query;
 use ecs::traits::ComponentQuery;
 use world::World;
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum AiKind {
-    Wait,
-    SeekTarget,
-    Follow,
-    Guard,
+macro_attr! {
+    #[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize, EnumFromStr!)]
+    pub enum AiKind {
+        Wait,
+        SeekTarget,
+        Follow,
+        Guard,
+    }
 }
 
 impl AiKind {
@@ -69,16 +73,44 @@ impl AiGoal {
     }
 }
 
+/// Goes after the nearest living chara from a hostile faction. AIs without a team of their own
+/// go after the player, like they always did.
 fn get_default_goal(entity: Entity, world: &World) -> Target {
-    let ai_compo = world.ecs().ais.get_or_err(entity);
-    let ai = &ai_compo.data;
+    let target = if world.team(entity).is_some() {
+        nearest_enemy(entity, world)
+    } else {
+        world.player()
+    };
 
-    match world.player() {
-        Some(p) => attack_target(p),
+    match target {
+        Some(t) => attack_target(t),
         None => Target::new(AiGoal::Wander),
     }
 }
 
+fn nearest_enemy(entity: Entity, world: &World) -> Option<Entity> {
+    let pos = match world.position(entity) {
+        Some(p) => p.pos,
+        None => return None,
+    };
+
+    let mut nearest: Option<(Entity, f32)> = None;
+    for (other, _) in query::iter(&world.ecs().charas) {
+        if !world.are_hostile(entity, other) {
+            continue;
+        }
+        let alive = world.ecs().healths.get(other).map_or(true, |h| h.hit_points > 0);
+        let dist = match world.position(other) {
+            Some(p) if alive => pos.distance(&p.pos),
+            _ => continue,
+        };
+        if nearest.map_or(true, |(_, d)| dist < d) {
+            nearest = Some((other, dist));
+        }
+    }
+    nearest.map(|(e, _)| e)
+}
+
 fn attack_target(entity: Entity) -> Target {
     Target {
         obj: TargetObject::Entity(entity),
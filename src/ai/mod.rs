@@ -17,8 +17,10 @@ use goap::*;
 use point::Direction;
 
 use ai::sensors::Sensor;
+use ecs::query;
 use ecs::traits::ComponentQuery;
 use point::*;
+use lua;
 use world::World;
 use util;
 use toml;
@@ -27,6 +29,8 @@ use toml;
 pub enum Action {
     Go(Direction),
     Shoot(f32),
+    Reload,
+    Melee(f32),
     Wait
 }
 
@@ -49,12 +53,28 @@ impl Ai {
             data: AiData::new(),
         }
     }
+
+    pub fn debug_info(&self) -> String {
+        self.data.debug_info()
+    }
 }
 
 make_global!(AI_PLANNER, AiPlanner, planner_from_toml());
 
+/// Rereads `data/actions.toml`. If it doesn't parse, the error is logged and the old planner is
+/// kept.
 pub fn reload_planner() {
-    instance::with_mut(|planner| *planner = planner_from_toml());
+    match try_planner_from_toml(ACTIONS_PATH) {
+        Ok(new_planner) => instance::with_mut(|planner| *planner = new_planner),
+        Err(e) => log!("Couldn't reload AI actions: {}", e),
+    }
+}
+
+/// Makes every AI pick its next action again, for after the planner changed.
+pub fn replan_all(world: &World) {
+    for (entity, _) in query::iter(&world.ecs().ais) {
+        update_next_action(entity, world);
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -163,16 +183,26 @@ thread_local! {
 }
 
 
-pub fn run(entity: Entity, world: &World, recheck: bool) -> Option<Action> {
-    if !world.ecs().ais.has(entity) {
-        return None;
-    }
+/// Updates what every AI is after and what it knows about it. The Lua sensors are run for all of
+/// them in a single call.
+pub fn recheck_all(world: &World) {
+    let ais: Vec<Entity> = query::iter(&world.ecs().ais).map(|(entity, _)| entity).collect();
 
-    if recheck {
+    for &entity in ais.iter() {
         check_target(entity, world);
         update_goal(entity, world);
         //check_triggers(entity, world);
-        update_memory(entity, world);
+    }
+
+    let script_facts = sense_scripts(world, &ais);
+    for (entity, facts) in ais.into_iter().zip(script_facts.into_iter()) {
+        update_memory(entity, world, facts);
+    }
+}
+
+pub fn run(entity: Entity, world: &World) -> Option<Action> {
+    if !world.ecs().ais.has(entity) {
+        return None;
     }
 
     let action = choose_action(entity, world);
@@ -180,12 +210,54 @@ pub fn run(entity: Entity, world: &World, recheck: bool) -> Option<Action> {
     Some(action)
 }
 
-fn update_memory(entity: Entity, world: &World) {
+/// The Lua sensors that the conditions of some action refer to. The planner never looks at the
+/// others, so there's no point in running them.
+fn script_props() -> Vec<String> {
+    instance::with(|planner| {
+        let mut names = Vec::new();
+        for action in planner.get_actions().into_iter() {
+            let effects = planner.actions(action);
+            for prop in effects.preconditions.keys().chain(effects.postconditions.keys()) {
+                if let AiProp::Script(ref name) = *prop {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            }
+        }
+        names
+    })
+}
+
+/// What the Lua sensors in use say about each of `ais`, in the same order.
+fn sense_scripts(world: &World, ais: &[Entity]) -> Vec<AiFacts> {
+    profile_scope!("ai_sense_lua");
+    let names = script_props();
+    if !names.is_empty() {
+        lua::world::refresh(world);
+    }
+    let thinkers: Vec<(Entity, Option<Entity>)> = ais.iter()
+        .map(|&entity| (entity, target_entity(world.ecs().ais.get_or_err(entity))))
+        .collect();
+
+    lua::ai::sense_all(&names, &thinkers)
+        .into_iter()
+        .map(|results| {
+            let mut facts = AiFacts::new();
+            for (name, result) in names.iter().zip(results.into_iter()) {
+                facts.insert(AiProp::Script(name.clone()), result);
+            }
+            facts
+        })
+        .collect()
+}
+
+fn update_memory(entity: Entity, world: &World, script_facts: AiFacts) {
+    profile_scope!("ai_sense");
     let ai = world.ecs().ais.get_or_err(entity);
-    let wants_to_know = all_props();
-    let mut new_memory = AiMemory { facts: GoapFacts::new() };
+    let mut new_memory = AiMemory { facts: script_facts };
 
-    for fact in wants_to_know.iter() {
+    for fact in all_props().iter() {
         SENSORS.with(|s| {
             let sensor = match s.get(fact) {
                 Some(f) => f,
@@ -213,12 +285,27 @@ fn update_memory(entity: Entity, world: &World) {
 }
 
 pub fn update_next_action(entity: Entity, world: &World) {
+    profile_scope!("ai_plan");
     let ai = world.ecs().ais.get_or_err(entity);
     let next_action = ai.data.get_next_action();
     log!("Do thing: {:?}", next_action);
     *ai.data.next_action.borrow_mut() = next_action;
 }
 
+/// Gives up on the current target, so the AI picks a new goal and plans for it.
+pub fn drop_goal(entity: Entity, world: &World) {
+    finish_target(entity, world);
+    update_next_action(entity, world);
+}
+
+/// The entity the AI is after, if its current target is one.
+fn target_entity(ai: &Ai) -> Option<Entity> {
+    ai.data.targets.borrow().peek().and_then(|t| match t.obj {
+        TargetObject::Entity(e) => Some(e),
+        _ => None,
+    })
+}
+
 fn check_target(entity: Entity, world: &World) {
     // The entity reference could go stale, so make sure it isn't.
     // TODO: Should this have to happen every time an entity reference is held
@@ -314,46 +401,78 @@ fn set_goal(entity: Entity, world: &World, desired: AiFacts, target_opt: Option<
 
 type AiPlanner = GoapPlanner<AiProp, bool, AiAction>;
 
-// TODO: Reverse priorities, so larger priorities are more important
+pub const ACTIONS_PATH: &str = "data/actions.toml";
+
 fn planner_from_toml() -> AiPlanner {
+    try_planner_from_toml(ACTIONS_PATH).unwrap()
+}
+
+// TODO: Reverse priorities, so larger priorities are more important
+fn try_planner_from_toml(filename: &str) -> Result<AiPlanner, String> {
+    let value = util::toml::try_toml_value_from_file(filename)?;
+    try_planner_from_value(&value)
+}
+
+fn try_planner_from_value(value: &toml::Value) -> Result<AiPlanner, String> {
+    // Scripts register their sensors and actions as they are loaded, which is the first time Lua
+    // is used.
+    lua::with(|_| ());
+
     let mut actions = HashMap::new();
-    let value = util::toml::toml_value_from_file("./data/actions.toml");
-    if let toml::Value::Array(ref array) = value["action"] {
-        for action in array.iter() {
-            // Parse string as enum
-            let name = action["name"]
-                .clone()
-                .try_into::<String>()
-                .unwrap()
-                .parse::<AiAction>()
-                .unwrap();
-            let cost = action["cost"].clone().try_into::<u32>().unwrap();
-            let mut effects = GoapEffects::new(cost);
-
-            // TODO: crashes if action.pre or action.post don't exist
-            // Read preconditions
-            if let toml::Value::Table(ref pre_table) = action["pre"] {
-                for (pre_name, pre_value) in pre_table.iter() {
-                    let key = pre_name.parse::<AiProp>().unwrap();
-                    let value = pre_value.clone().try_into::<bool>().unwrap();
-                    effects.set_precondition(key, value);
-                }
+    let array = match util::toml::get_value_in_table(value, "action") {
+        Some(&toml::Value::Array(ref array)) => array,
+        _ => return Ok(GoapPlanner { actions: actions }),
+    };
+
+    for action in array.iter() {
+        let name = util::toml::get_value_in_table(action, "name")
+            .and_then(|n| n.as_str())
+            .ok_or("AI action without a name".to_string())?;
+        // Names that aren't built in parse as script actions, so this never fails.
+        let name = name.parse::<AiAction>().unwrap();
+        if let AiAction::Script(ref script) = name {
+            if !lua::ai::has_action(script) {
+                return Err(format!("AI action {} isn't built in or defined by a script", script));
             }
+        }
 
-            // Read postconditions
-            if let toml::Value::Table(ref post_table) = action["post"] {
-                for (post_name, post_value) in post_table.iter() {
-                    let key = post_name.parse::<AiProp>().unwrap();
-                    let value = post_value.clone().try_into::<bool>().unwrap();
-                    effects.set_postcondition(key, value);
-                }
+        let cost = util::toml::get_value_in_table(action, "cost")
+            .and_then(|c| c.as_integer())
+            .ok_or(format!("AI action {:?} has no cost", name))?;
+        let mut effects = GoapEffects::new(cost as u32);
+
+        if let Some(&toml::Value::Table(ref pre_table)) = util::toml::get_value_in_table(action, "pre") {
+            for (pre_name, pre_value) in pre_table.iter() {
+                let (key, value) = parse_condition(&name, pre_name, pre_value)?;
+                effects.set_precondition(key, value);
             }
+        }
 
-            actions.insert(name, effects);
+        if let Some(&toml::Value::Table(ref post_table)) = util::toml::get_value_in_table(action, "post") {
+            for (post_name, post_value) in post_table.iter() {
+                let (key, value) = parse_condition(&name, post_name, post_value)?;
+                effects.set_postcondition(key, value);
+            }
         }
+
+        actions.insert(name, effects);
     }
 
-    GoapPlanner { actions: actions }
+    Ok(GoapPlanner { actions: actions })
+}
+
+fn parse_condition(action: &AiAction, name: &str, value: &toml::Value) -> Result<(AiProp, bool), String> {
+    // Names that aren't built in parse as script sensors, so this never fails.
+    let key = name.parse::<AiProp>().unwrap();
+    if let AiProp::Script(ref script) = key {
+        if !lua::ai::has_sensor(script) {
+            return Err(format!("Condition {} of AI action {:?} isn't built in or sensed by a script",
+                               script, action));
+        }
+    }
+    let value = value.as_bool()
+        .ok_or(format!("Condition {} of AI action {:?} isn't true or false", name, action))?;
+    Ok((key, value))
 }
 
 use std::collections::BinaryHeap;
@@ -452,3 +571,78 @@ impl Targets {
         self.targets.push(target);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planner_errors() {
+        let good = util::toml::toml_value_from_string("
+[[action]]
+name=\"Wait\"
+cost=1
+[action.pre]
+[action.post]
+Moving=false
+");
+        assert!(try_planner_from_value(&good).is_ok());
+
+        let bad = util::toml::toml_value_from_string("
+[[action]]
+name=\"Wait\"
+cost=1
+[action.post]
+Moving=\"nope\"
+");
+        assert!(try_planner_from_value(&bad).is_err());
+
+        let typo = util::toml::toml_value_from_string("
+[[action]]
+name=\"Wait\"
+cost=1
+[action.post]
+Movnig=false
+");
+        assert!(try_planner_from_value(&typo).is_err());
+
+        let script = util::toml::toml_value_from_string("
+[[action]]
+name=\"Wander\"
+cost=1
+[action.pre]
+TargetFar=true
+");
+        assert!(try_planner_from_value(&script).is_ok());
+    }
+
+    #[test]
+    fn test_reload_before_shooting() {
+        let planner = try_planner_from_toml(ACTIONS_PATH).unwrap();
+        let mut facts = default_ai_facts();
+        facts.insert(AiProp::HasTarget, true);
+        facts.insert(AiProp::TargetInRange, true);
+        facts.insert(AiProp::HasAmmo, false);
+
+        let memory = GoapState { facts: facts };
+        let goal = GoapState { facts: AiGoal::KillTarget.get_end_state() };
+        let plan = planner.get_plan(&memory, &goal).unwrap();
+        assert_eq!(plan, vec![AiAction::Reload, AiAction::ShootAt]);
+    }
+
+    #[test]
+    fn test_walk_into_melee_range() {
+        let planner = try_planner_from_toml(ACTIONS_PATH).unwrap();
+        let mut facts = default_ai_facts();
+        facts.insert(AiProp::HasTarget, true);
+        facts.insert(AiProp::TargetInRange, true);
+        facts.insert(AiProp::HasAmmo, false);
+        facts.insert(AiProp::HasReserveAmmo, false);
+        facts.insert(AiProp::CanDoMelee, true);
+
+        let memory = GoapState { facts: facts };
+        let goal = GoapState { facts: AiGoal::KillTarget.get_end_state() };
+        let plan = planner.get_plan(&memory, &goal).unwrap();
+        assert_eq!(plan, vec![AiAction::MoveIntoMelee, AiAction::SwingAt]);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
 use alga::linear::EuclideanSpace;
 use calx_ecs::Entity;
@@ -11,13 +12,29 @@ use super::{Ai, AiFacts, Target};
 
 macro_rules! generate_sensors {
     ( $( $prop:ident, $default:expr, $sensor:ident );+ $(;)*) => {
-        macro_attr! {
-        #[derive(Serialize, Deserialize, Hash, Ord, PartialOrd, Eq, PartialEq, Debug, Clone, EnumFromStr!)]
+        #[derive(Serialize, Deserialize, Hash, Ord, PartialOrd, Eq, PartialEq, Debug, Clone)]
         pub enum AiProp {
             $(
                 $prop,
             )*
+
+            /// Sensed by a Lua function registered with `ai.sensor`.
+            Script(String),
         }
+
+        impl FromStr for AiProp {
+            type Err = ();
+
+            /// Names that aren't built in are taken to be Lua sensors. Whether a script
+            /// actually senses them is checked when the planner loads.
+            fn from_str(s: &str) -> Result<AiProp, ()> {
+                match s {
+                    $(
+                        stringify!($prop) => Ok(AiProp::$prop),
+                    )*
+                    _ => Ok(AiProp::Script(s.to_string())),
+                }
+            }
         }
 
         pub(super) fn default_ai_facts() -> AiFacts {
@@ -60,6 +77,12 @@ generate_sensors! {
     TargetInRange, false, sense_target_in_range;
     TargetClose, false, sense_target_close;
 
+    NextToTarget, false, sense_next_to_target;
+    CanDoMelee, false, sense_can_do_melee;
+
+    HasAmmo, true, sense_has_ammo;
+    HasReserveAmmo, true, sense_has_reserve_ammo;
+
     Exists, true, sense_always_true;
     Moving, false, sense_always_false;
 }
@@ -188,6 +211,28 @@ fn sense_health_low(world: &World, entity: Entity, _ai: &Ai) -> bool {
 //          .any(|i| world.is_item(*i) && i.basename(world) == "watermelon")
 // }
 
+fn sense_next_to_target(world: &World, entity: Entity, ai: &Ai) -> bool {
+    let range = match world.ecs().melees.get(entity) {
+        Some(melee) => melee.range,
+        None => return false,
+    };
+    ai.data.targets.borrow().peek().map_or(false, |t| target_within_dist(world, entity, t, range))
+}
+
+fn sense_can_do_melee(world: &World, entity: Entity, _ai: &Ai) -> bool {
+    world.ecs().melees.has(entity)
+}
+
+fn sense_has_ammo(world: &World, entity: Entity, _ai: &Ai) -> bool {
+    world.held_gun(entity)
+         .map_or(false, |gun| world.ecs().guns.map_or(false, |g| g.has_ammo(), gun))
+}
+
+fn sense_has_reserve_ammo(world: &World, entity: Entity, _ai: &Ai) -> bool {
+    world.held_gun(entity)
+         .map_or(false, |gun| world.ecs().guns.map_or(false, |g| g.reserve_ammo > 0, gun))
+}
+
 fn sense_always_true(_world: &World, _entity: Entity, _ai: &Ai) -> bool {
     true
 }
@@ -0,0 +1,510 @@
+//! The developer console: a window for typing commands at the running game.
+
+use calx_ecs::Entity;
+use imgui::{self, ImString, Ui};
+
+use debug;
+use ecs::prefab;
+use engine::bindings::{self, InputAction, Trigger};
+use lua;
+use point::Point;
+use world::{Event, World};
+
+const INPUT_CAPACITY: usize = 256;
+const HISTORY_SIZE: usize = 100;
+
+pub const COMMANDS: [&str; 11] = [
+    "help", "spawn", "kill", "give", "set", "tp", "god", "timescale", "bind", "unbind", "savebinds",
+];
+
+const HELP: &str = "\
+spawn <prefab> [x z]  spawn a prefab, by default where the player is
+kill [entity]         kill the player, the selected entity or an entity by number
+give <prefab>         spawn a prefab and have the player hold it
+set <var> <value>     change a debug variable
+tp <x> <z>            move the player
+god                   toggle taking no damage
+timescale [scale]     show or change how fast the game runs
+bind <action> <key>   also trigger an input action with a key or mouse button
+unbind <action|key>   clear an action's bindings, or take a key off every action
+savebinds             write the current bindings to data/bindings.toml";
+
+/// Which entity a command acts on. Numbers are the ones the inspector shows, which are also the
+/// handles scripts use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntityRef {
+    Player,
+    Selected,
+    Handle(u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleCommand {
+    Help,
+    Spawn(String, Option<(f32, f32)>),
+    Kill(EntityRef),
+    Give(String),
+    Set(String, f32),
+    Tp(f32, f32),
+    God,
+    TimeScale(Option<f32>),
+    Bind(InputAction, Trigger),
+    Unbind(Trigger),
+    UnbindAction(InputAction),
+    SaveBindings,
+}
+
+impl ConsoleCommand {
+    /// Whether the command changes how the game plays out, which a replay couldn't reproduce.
+    /// Replays record keys, so rebinding counts.
+    pub fn changes_world(&self) -> bool {
+        match *self {
+            ConsoleCommand::Help |
+            ConsoleCommand::TimeScale(_) |
+            ConsoleCommand::SaveBindings => false,
+            ConsoleCommand::Spawn(..) |
+            ConsoleCommand::Kill(_) |
+            ConsoleCommand::Give(_) |
+            ConsoleCommand::Set(..) |
+            ConsoleCommand::Tp(..) |
+            ConsoleCommand::God |
+            ConsoleCommand::Bind(..) |
+            ConsoleCommand::Unbind(_) |
+            ConsoleCommand::UnbindAction(_) => true,
+        }
+    }
+}
+
+/// Keys the console handles itself instead of leaving them to the input line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsoleKey {
+    Complete,
+    Previous,
+    Next,
+}
+
+fn parse_arg<T: ::std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let arg = arg.ok_or(format!("Missing {}", what))?;
+    arg.parse::<T>().map_err(|_| format!("Invalid {} \"{}\"", what, arg))
+}
+
+fn parse_entity(arg: Option<&str>) -> Result<EntityRef, String> {
+    match arg {
+        None | Some("selected") => Ok(EntityRef::Selected),
+        Some("player") => Ok(EntityRef::Player),
+        Some(n) => n.parse::<u32>()
+            .map(EntityRef::Handle)
+            .map_err(|_| format!("Invalid entity \"{}\"", n)),
+    }
+}
+
+fn parse_trigger(arg: Option<&str>) -> Result<Trigger, String> {
+    let arg = arg.ok_or("Missing key".to_string())?;
+    Trigger::parse(arg).ok_or(format!("Invalid key \"{}\"", arg))
+}
+
+pub fn parse(line: &str) -> Result<ConsoleCommand, String> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Err("Empty command".to_string()),
+    };
+
+    let command = match name {
+        "help" => ConsoleCommand::Help,
+        "spawn" => {
+            let prefab = parse_arg::<String>(words.next(), "prefab")?;
+            let pos = match words.next() {
+                Some(x) => {
+                    let x = parse_arg(Some(x), "x")?;
+                    let z = parse_arg(words.next(), "z")?;
+                    Some((x, z))
+                },
+                None => None,
+            };
+            ConsoleCommand::Spawn(prefab, pos)
+        },
+        "kill" => ConsoleCommand::Kill(parse_entity(words.next())?),
+        "give" => ConsoleCommand::Give(parse_arg(words.next(), "prefab")?),
+        "set" => {
+            let var = parse_arg(words.next(), "variable")?;
+            ConsoleCommand::Set(var, parse_arg(words.next(), "value")?)
+        },
+        "tp" => {
+            let x = parse_arg(words.next(), "x")?;
+            ConsoleCommand::Tp(x, parse_arg(words.next(), "z")?)
+        },
+        "god" => ConsoleCommand::God,
+        "timescale" => match words.next() {
+            Some(scale) => ConsoleCommand::TimeScale(Some(parse_arg(Some(scale), "scale")?)),
+            None => ConsoleCommand::TimeScale(None),
+        },
+        "bind" => {
+            let action = parse_arg(words.next(), "action")?;
+            ConsoleCommand::Bind(action, parse_trigger(words.next())?)
+        },
+        "unbind" => match words.next() {
+            Some(arg) => match arg.parse::<InputAction>() {
+                Ok(action) => ConsoleCommand::UnbindAction(action),
+                Err(_) => ConsoleCommand::Unbind(parse_trigger(Some(arg))?),
+            },
+            None => return Err("Missing action or key".to_string()),
+        },
+        "savebinds" => ConsoleCommand::SaveBindings,
+        _ => return Err(format!("Unknown command \"{}\"", name)),
+    };
+
+    match words.next() {
+        Some(extra) => Err(format!("Unexpected \"{}\"", extra)),
+        None => Ok(command),
+    }
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix = words[0].clone();
+    for word in words.iter().skip(1) {
+        while !word.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+/// Completes the last word of `line`: command names first, then prefab names or debug variables
+/// depending on the command. Returns the completed line and, if the word could be more than one
+/// thing, what it could be.
+pub fn complete(line: &str, prefabs: &[String], vars: &[String]) -> (String, Vec<String>) {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if line.is_empty() || line.ends_with(' ') {
+        words.push("");
+    }
+
+    let options: Vec<String> = match (words.len(), words[0]) {
+        (1, _) => COMMANDS.iter().map(|c| c.to_string()).collect(),
+        (2, "spawn") | (2, "give") => prefabs.to_vec(),
+        (2, "set") => vars.to_vec(),
+        _ => Vec::new(),
+    };
+
+    let word = words.pop().unwrap();
+    let mut candidates: Vec<String> = options.into_iter().filter(|o| o.starts_with(word)).collect();
+    candidates.sort();
+
+    let completed = match candidates.len() {
+        0 => return (line.to_string(), candidates),
+        1 => format!("{} ", candidates[0]),
+        _ => common_prefix(&candidates),
+    };
+
+    let mut completed_line = words.join(" ");
+    if !completed_line.is_empty() {
+        completed_line.push(' ');
+    }
+    completed_line.push_str(&completed);
+
+    if candidates.len() == 1 {
+        (completed_line, Vec::new())
+    } else {
+        (completed_line, candidates)
+    }
+}
+
+fn resolve(entity: EntityRef, world: &World, selected: Option<Entity>) -> Result<Entity, String> {
+    let found = match entity {
+        EntityRef::Player => world.player(),
+        EntityRef::Selected => selected,
+        EntityRef::Handle(handle) => lua::world::entity_for(handle),
+    };
+    match found {
+        Some(entity) if world.contains(entity) => Ok(entity),
+        _ => Err("No such entity".to_string()),
+    }
+}
+
+fn player_pos(world: &World) -> Result<Point, String> {
+    world.player()
+        .and_then(|p| world.position(p))
+        .map(|p| p.pos)
+        .ok_or("There is no player".to_string())
+}
+
+/// Runs a command and returns what to print. Commands that change the world are refused while a
+/// replay is being recorded or played.
+pub fn execute(command: ConsoleCommand, world: &mut World, selected: Option<Entity>) -> Result<String, String> {
+    if command.changes_world() && debug::replay_active() {
+        return Err("Can't change the world while a replay is recording or playing".to_string());
+    }
+
+    match command {
+        ConsoleCommand::Help => Ok(HELP.to_string()),
+        ConsoleCommand::Spawn(prefab, pos) => {
+            let pos = match pos {
+                Some((x, z)) => Point::new(x, 0.0, z),
+                None => player_pos(world)?,
+            };
+            world.spawn_prefab(&prefab, pos)
+                .map(|e| format!("Spawned {} as {}", prefab, lua::world::handle_for(e)))
+                .ok_or(format!("Couldn't spawn {}", prefab))
+        },
+        ConsoleCommand::Kill(entity) => {
+            let entity = resolve(entity, world, selected)?;
+            world.push_event(Event::Killed { by: None }, entity);
+            Ok(format!("Killed {}", lua::world::handle_for(entity)))
+        },
+        ConsoleCommand::Give(prefab) => {
+            let player = world.player().ok_or("There is no player".to_string())?;
+            let pos = player_pos(world)?;
+            let item = world.spawn_prefab(&prefab, pos).ok_or(format!("Couldn't spawn {}", prefab))?;
+            if !world.equip(player, item) {
+                world.remove(item);
+                return Err(format!("{} isn't something that can be held", prefab));
+            }
+            Ok(format!("Gave the player {}", prefab))
+        },
+        ConsoleCommand::Set(var, value) => {
+            if debug::set(&var, value) {
+                Ok(format!("{} = {}", var, value))
+            } else {
+                Err(format!("No debug variable named {}", var))
+            }
+        },
+        ConsoleCommand::Tp(x, z) => {
+            let player = world.player().ok_or("There is no player".to_string())?;
+            let pos = Point::new(x, 0.0, z);
+            if !world.in_bounds(&pos) {
+                return Err(format!("{} {} is outside the world", x, z));
+            }
+            if let Some(position) = world.ecs_mut().positions.get_mut(player) {
+                position.pos = pos;
+                position.last_pos = pos;
+            }
+            Ok(format!("Moved the player to {} {}", x, z))
+        },
+        ConsoleCommand::God => {
+            let god = !debug::god_mode();
+            debug::set_god_mode(god);
+            Ok(format!("God mode {}", if god { "on" } else { "off" }))
+        },
+        ConsoleCommand::TimeScale(None) => Ok(format!("Time scale is {}", debug::time_scale())),
+        ConsoleCommand::TimeScale(Some(scale)) => {
+            if scale < 0.0 {
+                return Err("The time scale can't be negative".to_string());
+            }
+            debug::set_time_scale(scale);
+            Ok(format!("Time scale is now {}", scale))
+        },
+        ConsoleCommand::Bind(action, trigger) => {
+            bindings::with_mut(|b| b.bind(action, trigger));
+            let others: Vec<InputAction> = bindings::with(|b| b.conflicts())
+                .into_iter()
+                .filter(|&(t, _)| t == trigger)
+                .flat_map(|(_, actions)| actions.into_iter())
+                .filter(|a| *a != action)
+                .collect();
+            if others.is_empty() {
+                Ok(format!("Bound {} to {:?}", trigger, action))
+            } else {
+                Ok(format!("Bound {} to {:?}, but it's also bound to {:?}",
+                           trigger, action, others))
+            }
+        },
+        ConsoleCommand::Unbind(trigger) => {
+            bindings::with_mut(|b| b.unbind(trigger));
+            Ok(format!("Unbound {}", trigger))
+        },
+        ConsoleCommand::UnbindAction(action) => {
+            bindings::with_mut(|b| b.unbind_action(action));
+            Ok(format!("Unbound {:?}", action))
+        },
+        ConsoleCommand::SaveBindings => {
+            bindings::with(|b| b.save(bindings::BINDINGS_PATH))
+                .map(|_| format!("Saved bindings to {}", bindings::BINDINGS_PATH))
+                .map_err(|e| format!("Couldn't save bindings: {}", e))
+        },
+    }
+}
+
+fn input_buffer(s: &str) -> ImString {
+    let mut buf = ImString::with_capacity(INPUT_CAPACITY);
+    buf.push_str(s);
+    buf
+}
+
+pub struct Console {
+    input: ImString,
+    output: String,
+    history: Vec<String>,
+    /// How far back in the history the input line is, if it came from there.
+    history_pos: Option<usize>,
+    keys: Vec<ConsoleKey>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            input: input_buffer(""),
+            output: String::new(),
+            history: Vec::new(),
+            history_pos: None,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn print(&mut self, s: &str) {
+        self.output.push_str(s);
+        self.output.push('\n');
+    }
+
+    pub fn press(&mut self, key: ConsoleKey) {
+        self.keys.push(key);
+    }
+
+    fn handle_key(&mut self, key: ConsoleKey, vars: &[String]) {
+        match key {
+            ConsoleKey::Complete => {
+                let prefabs = prefab::with(|p| p.names());
+                let (line, candidates) = complete(self.input.to_str(), &prefabs, vars);
+                if !candidates.is_empty() {
+                    let candidates = candidates.join(" ");
+                    self.print(&candidates);
+                }
+                self.input = input_buffer(&line);
+            },
+            ConsoleKey::Previous => {
+                let pos = self.history_pos.map_or(0, |p| p + 1);
+                if pos < self.history.len() {
+                    self.history_pos = Some(pos);
+                    self.input = input_buffer(&self.history[self.history.len() - 1 - pos]);
+                }
+            },
+            ConsoleKey::Next => {
+                match self.history_pos {
+                    Some(0) | None => {
+                        self.history_pos = None;
+                        self.input = input_buffer("");
+                    },
+                    Some(pos) => {
+                        self.history_pos = Some(pos - 1);
+                        self.input = input_buffer(&self.history[self.history.len() - pos]);
+                    },
+                }
+            },
+        }
+    }
+
+    /// Draws the console and returns the lines entered since the last call.
+    pub fn run(&mut self, ui: &Ui, opened: &mut bool, vars: &[String]) -> Vec<String> {
+        let keys: Vec<ConsoleKey> = self.keys.drain(..).collect();
+        for key in keys {
+            self.handle_key(key, vars);
+        }
+
+        let mut entered = Vec::new();
+        let input = &mut self.input;
+        let output = &self.output;
+        ui.window(im_str!("Console"))
+            .opened(opened)
+            .size((500.0, 300.0), imgui::ImGuiCond::FirstUseEver)
+            .build(|| {
+                ui.child_frame(im_str!("output"), (0.0, -30.0))
+                    .scrollbar_horizontal(true)
+                    .build(|| {
+                        ui.text(output);
+                    });
+
+                let submitted = ui.input_text(im_str!("##command"), input)
+                    .enter_returns_true(true)
+                    .build();
+                if submitted {
+                    let line = input.to_str().trim().to_string();
+                    if !line.is_empty() {
+                        entered.push(line);
+                    }
+                    *input = input_buffer("");
+                }
+            });
+
+        for line in entered.iter() {
+            self.print(&format!("> {}", line));
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_SIZE {
+                self.history.remove(0);
+            }
+            self.history_pos = None;
+        }
+
+        entered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("spawn dood 5 6"), Ok(ConsoleCommand::Spawn("dood".to_string(), Some((5.0, 6.0)))));
+        assert_eq!(parse("spawn dood"), Ok(ConsoleCommand::Spawn("dood".to_string(), None)));
+        assert_eq!(parse("kill 3"), Ok(ConsoleCommand::Kill(EntityRef::Handle(3))));
+        assert_eq!(parse("kill"), Ok(ConsoleCommand::Kill(EntityRef::Selected)));
+        assert_eq!(parse("timescale 0.5"), Ok(ConsoleCommand::TimeScale(Some(0.5))));
+        assert!(parse("spawn dood 5").is_err());
+        assert!(parse("tp 1 2 3").is_err());
+        assert!(parse("dance").is_err());
+
+        let f = Trigger::parse("F").unwrap();
+        assert_eq!(parse("bind Jump F"), Ok(ConsoleCommand::Bind(InputAction::Jump, f)));
+        assert_eq!(parse("unbind Jump"), Ok(ConsoleCommand::UnbindAction(InputAction::Jump)));
+        assert_eq!(parse("unbind F"), Ok(ConsoleCommand::Unbind(f)));
+        assert!(parse("bind Dance F").is_err());
+        assert!(parse("bind Jump hyper+F").is_err());
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut world = World::new(32, 32, 1);
+        let f = Trigger::parse("F").unwrap();
+        assert!(execute(ConsoleCommand::Bind(InputAction::Jump, f), &mut world, None).is_ok());
+        assert!(bindings::with(|b| b.triggers(InputAction::Jump).contains(&f)));
+
+        assert!(execute(ConsoleCommand::Unbind(f), &mut world, None).is_ok());
+        assert!(!bindings::with(|b| b.triggers(InputAction::Jump).contains(&f)));
+
+        assert!(execute(ConsoleCommand::UnbindAction(InputAction::Jump), &mut world, None).is_ok());
+        assert!(bindings::with(|b| b.triggers(InputAction::Jump).is_empty()));
+    }
+
+    #[test]
+    fn test_no_changes_during_replays() {
+        let mut world = World::new(32, 32, 1);
+        debug::set_replay_active(true);
+        assert!(execute(ConsoleCommand::Kill(EntityRef::Player), &mut world, None).is_err());
+        assert!(execute(ConsoleCommand::Help, &mut world, None).is_ok());
+        debug::set_replay_active(false);
+        assert!(execute(ConsoleCommand::Kill(EntityRef::Player), &mut world, None).is_ok());
+    }
+
+    #[test]
+    fn test_give_only_items() {
+        let mut world = World::new(32, 32, 1);
+        let count = world.entities().len();
+        assert!(execute(ConsoleCommand::Give("wall".to_string()), &mut world, None).is_err());
+        assert_eq!(world.entities().len(), count);
+        assert!(execute(ConsoleCommand::Give("smg".to_string()), &mut world, None).is_ok());
+    }
+
+    #[test]
+    fn test_complete() {
+        let prefabs = vec!["dood".to_string(), "gun".to_string(), "smg".to_string()];
+        let vars = vec!["bullet_speed".to_string(), "bullet_time".to_string()];
+
+        assert_eq!(complete("sp", &prefabs, &vars), ("spawn ".to_string(), Vec::new()));
+        assert_eq!(complete("spawn d", &prefabs, &vars), ("spawn dood ".to_string(), Vec::new()));
+
+        let (line, candidates) = complete("set b", &prefabs, &vars);
+        assert_eq!(line, "set bullet_");
+        assert_eq!(candidates, vars);
+
+        assert_eq!(complete("tp 1", &prefabs, &vars), ("tp 1".to_string(), Vec::new()));
+    }
+}
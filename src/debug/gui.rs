@@ -2,6 +2,9 @@ use std::collections::{HashMap, VecDeque};
 use imgui;
 use toml::Value;
 
+use debug::console::Console;
+use debug::inspector::Inspector;
+use debug::profile::ProfilerWindow;
 use util;
 
 pub struct Variable {
@@ -22,50 +25,102 @@ impl Default for Variable {
 
 pub type Variables = HashMap<String, Variable>;
 
+pub struct SystemInfo {
+    pub name: String,
+    pub enabled: bool,
+    pub ms: f32,
+}
+
 pub struct UiState {
     pub show_log: bool,
+    pub show_inspector: bool,
+    pub show_console: bool,
+    pub show_profiler: bool,
+
+    /// Whether imgui is using the keyboard, so the game shouldn't.
+    pub wants_keyboard: bool,
+    pub god_mode: bool,
+    pub time_scale: f32,
+    /// Whether a replay is being recorded or played, so the world may only change through input.
+    pub replay_active: bool,
 
     pub fps: VecDeque<f32>,
     pub vars: Variables,
+    pub systems: Vec<SystemInfo>,
     pub log: LogWindow,
+    pub inspector: Inspector,
+    pub console: Console,
+    pub profiler: ProfilerWindow,
 }
 
-pub fn vars_from_toml() -> Variables {
-    let val = util::toml::toml_value_from_file("data/debug.toml");
+pub const VARS_PATH: &str = "data/debug.toml";
+
+pub fn try_vars_from_toml(filename: &str) -> Result<Variables, String> {
+    let val = util::toml::try_toml_value_from_file(filename)?;
     let mut vars = HashMap::new();
 
-    let keys = match util::toml::expect_value_in_table(&val, "keys") {
-        Value::Array(array) => array,
-        _                   => {
-            println!("No keys array found!");
-            return vars;
-        }
+    let keys = match util::toml::get_value_in_table(&val, "keys") {
+        Some(&Value::Array(ref array)) => array,
+        _ => return Err(format!("No keys array found in {}!", filename)),
     };
 
     for key in keys.iter() {
-        let name: String = util::toml::expect_value_in_table(key, "name");
-        let default: f32 = util::toml::expect_value_in_table(key, "default");
-        let min: f32 = util::toml::expect_value_in_table(key, "min");
-        let max: f32 = util::toml::expect_value_in_table(key, "max");
-
-        vars.insert(name, Variable { val: default, min: min, max: max });
+        let var: VariableDef = key.clone()
+            .try_into()
+            .map_err(|e| format!("Bad debug variable in {}: {}", filename, e))?;
+        vars.insert(var.name, Variable { val: var.default, min: var.min, max: var.max });
     }
-    vars
+    Ok(vars)
+}
+
+#[derive(Deserialize)]
+struct VariableDef {
+    name: String,
+    default: f32,
+    min: f32,
+    max: f32,
 }
 
 impl UiState {
     pub fn new() -> Self {
+        // This is what `log!` writes to, so it can't be used until the state exists.
+        let mut log = LogWindow::new();
+        let vars = match try_vars_from_toml(VARS_PATH) {
+            Ok(vars) => vars,
+            Err(e) => {
+                log.add(&format!("{}\n", e));
+                HashMap::new()
+            },
+        };
+
         UiState {
             show_log: false,
+            show_inspector: false,
+            show_console: false,
+            show_profiler: false,
+
+            wants_keyboard: false,
+            god_mode: false,
+            time_scale: 1.0,
+            replay_active: false,
 
             fps: VecDeque::new(),
-            vars: vars_from_toml(),
-            log: LogWindow::new(),
+            vars: vars,
+            systems: Vec::new(),
+            log: log,
+            inspector: Inspector::new(),
+            console: Console::new(),
+            profiler: ProfilerWindow::new(),
         }
     }
 
+    /// Rereads the variables from disk. On error the old ones are kept and the error goes to the
+    /// log window.
     pub fn reset_vars(&mut self) {
-        self.vars = vars_from_toml();
+        match try_vars_from_toml(VARS_PATH) {
+            Ok(vars) => self.vars = vars,
+            Err(e) => self.log.add(&format!("{}\n", e)),
+        }
     }
 }
 
@@ -0,0 +1,267 @@
+//! A window for looking at and changing the components of one entity while the game runs.
+
+use std::f32::consts::PI;
+
+use calx_ecs::Entity;
+use imgui::{self, Ui};
+
+use ai;
+use ecs::Ecs;
+use lua;
+use point::Point;
+use world::World;
+
+pub struct Inspector {
+    picking: bool,
+    pick: Option<(f32, f32)>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector {
+            picking: false,
+            pick: None,
+        }
+    }
+
+    /// Where the user clicked to pick an entity, in imgui's screen coordinates. The renderer
+    /// knows what is drawn there, so it does the picking.
+    pub fn take_pick(&mut self) -> Option<(f32, f32)> {
+        self.pick.take()
+    }
+
+    pub fn run(&mut self, ui: &Ui, opened: &mut bool, world: &mut World, selected: &mut Option<Entity>) {
+        // Clicks on imgui's own windows don't count.
+        if self.picking && !ui.want_capture_mouse() &&
+            ui.imgui().is_mouse_clicked(imgui::ImMouseButton::Left) {
+            self.pick = Some(ui.imgui().mouse_pos());
+            self.picking = false;
+        }
+
+        let picking = &mut self.picking;
+        ui.window(im_str!("Inspector"))
+            .opened(opened)
+            .size((350.0, 500.0), imgui::ImGuiCond::FirstUseEver)
+            .build(|| {
+                ui.checkbox(im_str!("Pick in world"), picking);
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("Player")) {
+                    *selected = world.player();
+                }
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("Clear")) {
+                    *selected = None;
+                }
+
+                if ui.collapsing_header(im_str!("Entities")).build() {
+                    for &entity in world.entities() {
+                        if ui.small_button(im_str!("{}", label(world, entity))) {
+                            *selected = Some(entity);
+                        }
+                    }
+                }
+
+                ui.separator();
+                match *selected {
+                    Some(entity) if world.contains(entity) => components(ui, world, entity, selected),
+                    _ => ui.text(im_str!("Nothing selected.")),
+                }
+            });
+    }
+}
+
+/// Entities are numbered by their script handles, which the console also takes.
+fn label(world: &World, entity: Entity) -> String {
+    let handle = lua::world::handle_for(entity);
+    match world.ecs().names.get(entity) {
+        Some(name) => format!("#{} {}", handle, name.name),
+        None => format!("#{}", handle),
+    }
+}
+
+fn edit_point(ui: &Ui, name: &str, point: &mut Point) {
+    let mut values = [point.x, point.y, point.z];
+    if ui.input_float3(im_str!("{}", name), &mut values).build() {
+        *point = Point::new(values[0], values[1], values[2]);
+    }
+}
+
+fn components(ui: &Ui, world: &mut World, entity: Entity, selected: &mut Option<Entity>) {
+    ui.text(im_str!("{}", label(world, entity)));
+
+    edit_components(ui, world.ecs_mut(), entity, selected);
+    edit_holds(ui, world, entity, selected);
+    edit_ai(ui, world, entity);
+}
+
+/// The components that can be changed in place.
+fn edit_components(ui: &Ui, ecs: &mut Ecs, entity: Entity, selected: &mut Option<Entity>) {
+    if let Some(pos) = ecs.positions.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Position")).build() {
+            edit_point(ui, "pos", &mut pos.pos);
+            ui.slider_float(im_str!("dir"), &mut pos.dir, -PI, PI).build();
+        }
+    }
+
+    if let Some(phys) = ecs.physics.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Physics")).build() {
+            let mut vel = [phys.vel.x, phys.vel.y, phys.vel.z];
+            if ui.input_float3(im_str!("vel"), &mut vel).build() {
+                phys.vel.x = vel[0];
+                phys.vel.y = vel[1];
+                phys.vel.z = vel[2];
+            }
+            let mut accel = [phys.accel.x, phys.accel.y, phys.accel.z];
+            if ui.input_float3(im_str!("accel"), &mut accel).build() {
+                phys.accel.x = accel[0];
+                phys.accel.y = accel[1];
+                phys.accel.z = accel[2];
+            }
+            ui.checkbox(im_str!("grounded"), &mut phys.grounded);
+            ui.input_float(im_str!("mass"), &mut phys.mass).build();
+            ui.slider_float(im_str!("restitution"), &mut phys.restitution, 0.0, 1.0).build();
+            ui.input_float(im_str!("friction"), &mut phys.friction).build();
+            ui.text(im_str!("{:?} {:?}, {} movement frames", phys.shape, phys.kind, phys.movement_frames));
+        }
+    }
+
+    if let Some(health) = ecs.healths.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Health")).build() {
+            ui.input_int(im_str!("hit points"), &mut health.hit_points).build();
+            ui.input_int(im_str!("max hit points"), &mut health.max_hit_points).build();
+        }
+    }
+
+    if let Some(armor) = ecs.armors.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Armor")).build() {
+            for (zone, resistances) in vec![("head", &mut armor.head), ("body", &mut armor.body)] {
+                for (kind, resistance) in resistances.iter_mut() {
+                    ui.slider_float(im_str!("{} {:?}", zone, kind), resistance, 0.0, 1.0).build();
+                }
+            }
+        }
+    }
+
+    if let Some(chara) = ecs.charas.get(entity) {
+        if ui.collapsing_header(im_str!("Chara")).build() {
+            ui.text(im_str!("team: {}", chara.team().unwrap_or("none")));
+        }
+    }
+
+    if let Some(gun) = ecs.guns.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Gun")).build() {
+            ui.text(im_str!("bullet: {}", gun.bullet));
+            ui.slider_float(im_str!("spread"), &mut gun.spread, 0.0, PI).build();
+            let mut clip_size = gun.clip_size as i32;
+            if ui.input_int(im_str!("clip size"), &mut clip_size).build() {
+                gun.clip_size = clip_size.max(0) as u16;
+            }
+            ui.input_float(im_str!("fire rate (s)"), &mut gun.fire_rate_secs).build();
+            ui.input_float(im_str!("reload time (s)"), &mut gun.reload_time_secs).build();
+            let mut ammo = gun.ammo as i32;
+            if ui.input_int(im_str!("ammo"), &mut ammo).build() {
+                gun.ammo = ammo.max(0).min(gun.clip_size as i32) as u16;
+            }
+            let mut reserve_ammo = gun.reserve_ammo as i32;
+            if ui.input_int(im_str!("reserve ammo"), &mut reserve_ammo).build() {
+                gun.reserve_ammo = reserve_ammo.max(0) as u16;
+            }
+            if gun.is_reloading() {
+                ui.text(im_str!("reloading, {:.2} s left", gun.reload_secs_left));
+            }
+            ui.checkbox(im_str!("shooting"), &mut gun.shooting);
+            ui.text(im_str!("refire in {:.2} s", gun.secs_to_refire));
+        }
+    }
+
+    if let Some(melee) = ecs.melees.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Melee")).build() {
+            ui.input_int(im_str!("damage"), &mut melee.damage).build();
+            ui.input_float(im_str!("range"), &mut melee.range).build();
+            ui.slider_float(im_str!("arc"), &mut melee.arc, 0.0, PI * 2.0).build();
+            ui.input_float(im_str!("knockback"), &mut melee.knockback).build();
+            ui.input_float(im_str!("cooldown (s)"), &mut melee.cooldown_secs).build();
+            ui.text(im_str!("ready in {:.2} s", melee.secs_to_ready));
+        }
+    }
+
+    if let Some(bullet) = ecs.bullets.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Bullet")).build() {
+            ui.input_int(im_str!("damage"), &mut bullet.damage).build();
+            ui.text(im_str!("kind: {:?}", bullet.kind));
+            ui.input_float(im_str!("time left"), &mut bullet.time_left).build();
+            if ui.small_button(im_str!("Fired by #{}", lua::world::handle_for(bullet.fired_by))) {
+                *selected = Some(bullet.fired_by);
+            }
+        }
+    }
+
+    if let Some(bomb) = ecs.bombs.get_mut(entity) {
+        if ui.collapsing_header(im_str!("Bomb")).build() {
+            ui.input_float(im_str!("time left"), &mut bomb.time_left).build();
+            ui.input_float(im_str!("blast radius"), &mut bomb.blast.radius).build();
+            ui.input_int(im_str!("blast damage"), &mut bomb.blast.damage).build();
+            if let Some(owner) = bomb.owner {
+                if ui.small_button(im_str!("Set by #{}", lua::world::handle_for(owner))) {
+                    *selected = Some(owner);
+                }
+            }
+        }
+    }
+
+    if let Some(inventory) = ecs.inventories.get(entity) {
+        if ui.collapsing_header(im_str!("Inventory")).build() {
+            ui.text(im_str!("{} of {} slots used", inventory.weapons.len(), inventory.slots));
+            for (i, &weapon) in inventory.weapons.iter().enumerate() {
+                let marker = if i == inventory.current { ">" } else { " " };
+                if ui.small_button(im_str!("{} #{}", marker, lua::world::handle_for(weapon))) {
+                    *selected = Some(weapon);
+                }
+            }
+        }
+    }
+}
+
+/// Whatever `entity` holds or is held by. Releasing goes through the world, so both sides of the
+/// link and any inventory are updated.
+fn edit_holds(ui: &Ui, world: &mut World, entity: Entity, selected: &mut Option<Entity>) {
+    let mut release = None;
+    if let Some(holds) = world.ecs().holds.get(entity) {
+        if ui.collapsing_header(im_str!("Holds")).build() {
+            for (&other, &is_holder) in holds.0.iter() {
+                // `is_holder` says whether this entity is the one holding `other`.
+                let role = if is_holder { "held" } else { "holder" };
+                let handle = lua::world::handle_for(other);
+                if ui.small_button(im_str!("#{} ({})", handle, role)) {
+                    *selected = Some(other);
+                }
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("Release##{}", handle)) {
+                    release = Some(if is_holder { (entity, other) } else { (other, entity) });
+                }
+            }
+        }
+    }
+
+    if let Some((holder, item)) = release {
+        world.unequip(holder, item);
+    }
+}
+
+fn edit_ai(ui: &Ui, world: &mut World, entity: Entity) {
+    let (mut drop_goal, mut replan) = (false, false);
+    if let Some(ai) = world.ecs().ais.get(entity) {
+        if ui.collapsing_header(im_str!("Ai")).build() {
+            drop_goal = ui.small_button(im_str!("Drop goal"));
+            ui.same_line(0.0);
+            replan = ui.small_button(im_str!("Replan"));
+            ui.text(im_str!("{}", ai.debug_info()));
+        }
+    }
+
+    if drop_goal {
+        ai::drop_goal(entity, world);
+    } else if replan {
+        ai::update_next_action(entity, world);
+    }
+}
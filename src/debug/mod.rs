@@ -2,7 +2,10 @@ use calx_ecs::Entity;
 use world::World;
 use imgui::{self, ImGui, Ui};
 
+pub mod console;
 pub mod gui;
+pub mod inspector;
+pub mod profile;
 
 use self::gui::*;
 
@@ -32,27 +35,127 @@ mod entity {
 }
 
 pub fn follow_entity(entity: Option<Entity>) {
-    entity::instance::with_mut(|e| *e = entity);
+    let changed = entity::instance::with_mut(|e| {
+        let changed = *e != entity;
+        *e = entity;
+        changed
+    });
+    if changed && entity.is_none() {
+        add_text(String::new());
+    }
+}
+
+pub fn followed_entity() -> Option<Entity> {
+    entity::instance::with(|e| *e)
 }
 
 fn entity_info(entity: Entity, world: &World) -> String {
-    String::new()
+    let ecs = world.ecs();
+    let mut info = match ecs.names.get(entity) {
+        Some(name) => format!("{} {:?}", name.name, entity),
+        None => format!("{:?}", entity),
+    };
+    if let Some(health) = ecs.healths.get(entity) {
+        info.push_str(&format!("\nhp: {}/{}", health.hit_points, health.max_hit_points));
+    }
+    if let Some(ai) = ecs.ais.get(entity) {
+        info.push_str(&format!("\n{}", ai.debug_info()));
+    }
+    info
 }
 
+/// Shows what the followed entity is up to, and stops following it once it is gone.
 pub fn update(world: &World) {
-    entity::instance::with_mut(|e| if e.map_or(true, |en| !world.contains(en)) {
-                                   *e = None;
-                                   add_text(String::new());
-                               });
-    entity::instance::with(|e| if let &Some(entity) = e {
-                               add_text(entity_info(entity, world));
-                           });
+    match followed_entity() {
+        Some(entity) if world.contains(entity) => add_text(entity_info(entity, world)),
+        Some(_) => follow_entity(None),
+        None => (),
+    }
+}
+
+/// Where the user clicked to pick an entity in the inspector, if they did since the last call.
+pub fn take_pick() -> Option<(f32, f32)> {
+    instance::with_mut(|state| state.inspector.take_pick())
 }
 
 pub fn get(key: &str) -> f32 {
     instance::with_mut(|state| state.vars.entry(key.to_string()).or_insert(Variable::default()).val)
 }
 
+/// Changes a debug variable. Returns false if there is no such variable.
+pub fn set(key: &str, val: f32) -> bool {
+    instance::with_mut(|state| match state.vars.get_mut(key) {
+        Some(var) => {
+            var.val = val;
+            true
+        },
+        None => false,
+    })
+}
+
+/// If true, the player takes no damage.
+pub fn god_mode() -> bool {
+    instance::with(|state| state.god_mode)
+}
+
+pub fn set_god_mode(god_mode: bool) {
+    instance::with_mut(|state| state.god_mode = god_mode);
+}
+
+/// How many seconds of game time pass per real second.
+pub fn time_scale() -> f32 {
+    instance::with(|state| state.time_scale)
+}
+
+pub fn set_time_scale(time_scale: f32) {
+    instance::with_mut(|state| state.time_scale = time_scale);
+}
+
+/// Whether a replay is being recorded or played back.
+pub fn replay_active() -> bool {
+    instance::with(|state| state.replay_active)
+}
+
+pub fn set_replay_active(replay_active: bool) {
+    instance::with_mut(|state| state.replay_active = replay_active);
+}
+
+pub fn wants_keyboard() -> bool {
+    instance::with(|state| state.wants_keyboard)
+}
+
+/// Passes a key to the console, if it is open.
+pub fn console_key(key: console::ConsoleKey) {
+    instance::with_mut(|state| if state.show_console {
+        state.console.press(key)
+    });
+}
+
+/// Rereads `data/debug.toml`, keeping the old variables if it doesn't parse.
+pub fn reload_vars() {
+    instance::with_mut(|state| state.reset_vars());
+}
+
+pub fn register_system(name: &str) {
+    instance::with_mut(|state| {
+        if !state.systems.iter().any(|s| s.name == name) {
+            state.systems.push(SystemInfo { name: name.to_string(), enabled: true, ms: 0.0 });
+        }
+    })
+}
+
+pub fn system_enabled(name: &str) -> bool {
+    instance::with(|state| state.systems.iter().find(|s| s.name == name).map_or(true, |s| s.enabled))
+}
+
+pub fn set_system_time(name: &str, ms: f32) {
+    instance::with_mut(|state| {
+        if let Some(system) = state.systems.iter_mut().find(|s| s.name == name) {
+            system.ms = ms;
+        }
+    })
+}
+
 pub fn set_fps(fps: f32) {
     instance::with_mut(|state| {
         if state.fps.len() > 15 {
@@ -70,11 +173,27 @@ pub fn log(s: &str) {
 
 make_global!(UI_STATE, gui::UiState, gui::UiState::new());
 
-pub fn run_ui(ui: &imgui::Ui) {
+pub fn run_ui(ui: &imgui::Ui, world: &mut World) {
+    let mut selected = followed_entity();
+    let mut entered = Vec::new();
     instance::with_mut(|state| {
+        state.wants_keyboard = ui.want_capture_keyboard();
+
+        if state.show_console {
+            let vars: Vec<String> = state.vars.keys().cloned().collect();
+            entered = state.console.run(ui, &mut state.show_console, &vars);
+        }
         if state.show_log {
             state.log.run(ui, &mut state.show_log);
         }
+        if state.show_inspector {
+            state.inspector.run(ui, &mut state.show_inspector, world, &mut selected);
+        }
+        if state.show_profiler {
+            if let Some(message) = state.profiler.run(ui, &mut state.show_profiler) {
+                state.log.add(&format!("{}\n", message));
+            }
+        }
         ui.window(im_str!("Hello world"))
             .size((300.0, 800.0), imgui::ImGuiCond::FirstUseEver)
             .menu_bar(true)
@@ -86,6 +205,15 @@ pub fn run_ui(ui: &imgui::Ui) {
                         ui.menu_item(im_str!("Log window"))
                             .selected(&mut state.show_log)
                             .build();
+                        ui.menu_item(im_str!("Inspector"))
+                            .selected(&mut state.show_inspector)
+                            .build();
+                        ui.menu_item(im_str!("Console"))
+                            .selected(&mut state.show_console)
+                            .build();
+                        ui.menu_item(im_str!("Profiler"))
+                            .selected(&mut state.show_profiler)
+                            .build();
                     });
                 });
 
@@ -96,6 +224,15 @@ pub fn run_ui(ui: &imgui::Ui) {
                 let mouse_pos = ui.imgui().mouse_pos();
                 ui.text(im_str!("Mouse Position: ({:.1},{:.1})", mouse_pos.0, mouse_pos.1));
 
+                ui.separator();
+                if ui.collapsing_header(im_str!("Systems")).build() {
+                    for system in state.systems.iter_mut() {
+                        ui.checkbox(im_str!("{}", system.name), &mut system.enabled);
+                        ui.same_line(200.0);
+                        ui.text(im_str!("{:.3} ms", system.ms));
+                    }
+                }
+
                 ui.separator();
                 if ui.small_button(im_str!("Reload")) {
                     state.reset_vars();
@@ -107,5 +244,19 @@ pub fn run_ui(ui: &imgui::Ui) {
                 }
             });
 
-    })
+    });
+    follow_entity(selected);
+
+    // Commands can touch the debug state themselves, so they run once the UI is done with it.
+    for line in entered {
+        let output = match console::parse(&line) {
+            Ok(command) => console::execute(command, world, selected),
+            Err(e) => Err(e),
+        };
+        let output = match output {
+            Ok(s) => s,
+            Err(e) => format!("Error: {}", e),
+        };
+        instance::with_mut(|state| state.console.print(&output));
+    }
 }
@@ -0,0 +1,318 @@
+//! Scoped timing of what happens during a frame, for finding out where the time goes.
+//!
+//! ```ignore
+//! fn update_physics(&mut self) {
+//!     profile_scope!("physics");
+//!     ...
+//! }
+//! ```
+//!
+//! Scopes nest, and only scopes opened between `begin_frame` and `end_frame` are recorded. The
+//! last few hundred frames are kept, and can be written out as a Chrome trace (open it in
+//! `chrome://tracing`).
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use imgui::{self, Ui};
+
+pub const MAX_FRAMES: usize = 300;
+pub const TRACE_PATH: &str = "profile.json";
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + (duration.subsec_nanos() / 1000) as u64
+}
+
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub name: &'static str,
+    pub depth: usize,
+    /// Microseconds since the profiler was created.
+    pub start: u64,
+    pub duration: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub number: u64,
+    pub start: u64,
+    pub duration: u64,
+    pub spans: Vec<Span>,
+}
+
+pub struct Profiler {
+    pub enabled: bool,
+    epoch: Instant,
+    frames: VecDeque<Frame>,
+    current: Option<Frame>,
+    /// The spans of the current frame that haven't ended yet, innermost last.
+    open: Vec<usize>,
+    frame_count: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            enabled: true,
+            epoch: Instant::now(),
+            frames: VecDeque::new(),
+            current: None,
+            open: Vec::new(),
+            frame_count: 0,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        micros(self.epoch.elapsed())
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_count += 1;
+        self.open.clear();
+        self.current = if self.enabled {
+            Some(Frame {
+                number: self.frame_count,
+                start: self.now(),
+                duration: 0,
+                spans: Vec::new(),
+            })
+        } else {
+            None
+        };
+    }
+
+    pub fn end_frame(&mut self) {
+        let now = self.now();
+        if let Some(mut frame) = self.current.take() {
+            frame.duration = now - frame.start;
+            if self.frames.len() >= MAX_FRAMES {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(frame);
+        }
+    }
+
+    fn enter(&mut self, name: &'static str) -> Option<usize> {
+        let now = self.now();
+        let depth = self.open.len();
+        let index = match self.current {
+            Some(ref mut frame) => {
+                frame.spans.push(Span { name: name, depth: depth, start: now, duration: 0 });
+                frame.spans.len() - 1
+            },
+            None => return None,
+        };
+        self.open.push(index);
+        Some(index)
+    }
+
+    fn exit(&mut self, index: usize) {
+        let now = self.now();
+        if let Some(ref mut frame) = self.current {
+            if let Some(span) = frame.spans.get_mut(index) {
+                span.duration = now - span.start;
+            }
+        }
+        self.open.retain(|&i| i != index);
+    }
+
+    /// The recorded frames, oldest first.
+    pub fn frames(&self) -> &VecDeque<Frame> {
+        &self.frames
+    }
+
+    pub fn write_chrome_trace<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut events = Vec::new();
+        for frame in self.frames.iter() {
+            let name = format!("frame {}", frame.number);
+            events.push(trace_event(&name, frame.start, frame.duration));
+            for span in frame.spans.iter() {
+                events.push(trace_event(span.name, span.start, span.duration));
+            }
+        }
+        write!(out, "{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
+}
+
+fn trace_event(name: &str, start: u64, duration: u64) -> String {
+    let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1}}",
+            name, start, duration)
+}
+
+make_global!(PROFILER, Profiler, Profiler::new());
+
+pub use self::instance::*;
+
+pub fn begin_frame() {
+    with_mut(|p| p.begin_frame());
+}
+
+pub fn end_frame() {
+    with_mut(|p| p.end_frame());
+}
+
+/// Writes every recorded frame to `path` as a Chrome trace.
+pub fn export(path: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    with(|p| p.write_chrome_trace(&mut file))
+}
+
+/// Times everything until it is dropped.
+pub struct Scope {
+    index: Option<usize>,
+    start: Instant,
+}
+
+impl Scope {
+    /// Ends the scope early and returns how long it took in milliseconds.
+    pub fn end(self) -> f32 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() as f32 * 1000.0 + elapsed.subsec_nanos() as f32 / 1_000_000.0
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(index) = self.index {
+            with_mut(|p| p.exit(index));
+        }
+    }
+}
+
+pub fn scope(name: &'static str) -> Scope {
+    Scope {
+        index: with_mut(|p| p.enter(name)),
+        start: Instant::now(),
+    }
+}
+
+const LABEL_WIDTH: f32 = 24.0;
+const ROW_HEIGHT: f32 = 18.0;
+
+pub struct ProfilerWindow {
+    /// Which frame to show, counting back from the newest one.
+    frames_back: i32,
+}
+
+impl ProfilerWindow {
+    pub fn new() -> Self {
+        ProfilerWindow { frames_back: 0 }
+    }
+
+    /// Draws the profiler. Returns a message for the log, if there is something to say.
+    pub fn run(&mut self, ui: &Ui, opened: &mut bool) -> Option<String> {
+        let mut message = None;
+        let frames_back = &mut self.frames_back;
+
+        ui.window(im_str!("Profiler"))
+            .opened(opened)
+            .size((700.0, 400.0), imgui::ImGuiCond::FirstUseEver)
+            .build(|| {
+                let mut enabled = with(|p| p.enabled);
+                if ui.checkbox(im_str!("Record"), &mut enabled) {
+                    with_mut(|p| p.enabled = enabled);
+                }
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("Export trace")) {
+                    message = Some(match export(TRACE_PATH) {
+                        Ok(()) => format!("Wrote a Chrome trace to {}", TRACE_PATH),
+                        Err(e) => format!("Couldn't write {}: {}", TRACE_PATH, e),
+                    });
+                }
+
+                with(|p| {
+                    let frames = p.frames();
+                    if frames.is_empty() {
+                        ui.text(im_str!("No frames recorded."));
+                        return;
+                    }
+
+                    let times: Vec<f32> = frames.iter().map(|f| f.duration as f32 / 1000.0).collect();
+                    imgui::PlotHistogram::new(ui, im_str!("ms/frame"), &times)
+                        .graph_size((0.0, 60.0))
+                        .build();
+
+                    let max_back = frames.len() as i32 - 1;
+                    ui.slider_int(im_str!("Frames back"), frames_back, 0, max_back).build();
+                    *frames_back = (*frames_back).min(max_back).max(0);
+
+                    let frame = &frames[frames.len() - 1 - *frames_back as usize];
+                    ui.text(im_str!("Frame {}: {:.2} ms", frame.number, frame.duration as f32 / 1000.0));
+                    ui.separator();
+                    flame_chart(ui, frame);
+                    ui.separator();
+                    breakdown(ui, frame);
+                });
+            });
+
+        message
+    }
+}
+
+/// Draws each span as a button as wide as the time it took, with nested spans on the rows below.
+fn flame_chart(ui: &Ui, frame: &Frame) {
+    let width = ui.get_window_size().0 - LABEL_WIDTH - 20.0;
+    let scale = width / frame.duration.max(1) as f32;
+    let depth = frame.spans.iter().map(|s| s.depth).max().unwrap_or(0);
+
+    for row in 0..depth + 1 {
+        ui.text(im_str!("{}", row));
+        for (i, span) in frame.spans.iter().enumerate().filter(|&(_, s)| s.depth == row) {
+            let x = LABEL_WIDTH + (span.start - frame.start) as f32 * scale;
+            let w = (span.duration as f32 * scale).max(1.0);
+            ui.same_line(x);
+            ui.button(im_str!("{}##{}", span.name, i), (w, ROW_HEIGHT));
+            if ui.is_item_hovered() {
+                ui.tooltip_text(im_str!("{}: {:.3} ms", span.name, span.duration as f32 / 1000.0));
+            }
+        }
+    }
+}
+
+/// Lists every span with how long it took, indented by how deeply it is nested.
+fn breakdown(ui: &Ui, frame: &Frame) {
+    for span in frame.spans.iter() {
+        let indent: String = (0..span.depth).map(|_| "  ").collect();
+        ui.text(im_str!("{}{:<24} {:8.3} ms", indent, span.name, span.duration as f32 / 1000.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting() {
+        let mut profiler = Profiler::new();
+        assert_eq!(profiler.enter("outside"), None);
+
+        profiler.begin_frame();
+        let outer = profiler.enter("outer").unwrap();
+        let inner = profiler.enter("inner").unwrap();
+        profiler.exit(inner);
+        profiler.exit(outer);
+        profiler.end_frame();
+
+        let frame = &profiler.frames()[0];
+        let depths: Vec<(&str, usize)> = frame.spans.iter().map(|s| (s.name, s.depth)).collect();
+        assert_eq!(depths, vec![("outer", 0), ("inner", 1)]);
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        let mut profiler = Profiler::new();
+        profiler.begin_frame();
+        let span = profiler.enter("say \"hi\"").unwrap();
+        profiler.exit(span);
+        profiler.end_frame();
+
+        let mut out = Vec::new();
+        profiler.write_chrome_trace(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("{\"traceEvents\":["));
+        assert!(out.contains("\"name\":\"say \\\"hi\\\"\",\"ph\":\"X\""));
+    }
+}
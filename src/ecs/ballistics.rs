@@ -0,0 +1,136 @@
+//! Stats for each kind of projectile, read from `data/bullets.toml`. A gun's `bullet` field names
+//! one of them.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+
+use ecs::components::DamageKind;
+use util;
+
+pub const BULLETS_PATH: &str = "data/bullets.toml";
+
+/// Damage dealt to everything around where a projectile hits.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Splash {
+    pub radius: f32,
+    /// The damage at the center, falling off to nothing at the edge.
+    pub damage: i32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BulletDef {
+    pub speed: f32,
+    pub damage: i32,
+    /// Seconds until the projectile disappears.
+    pub lifetime: f32,
+    /// How many projectiles come out with each shot, each with its own spread.
+    #[serde(default = "one")]
+    pub pellets: u32,
+    /// How many charas the projectile passes through before stopping.
+    #[serde(default)]
+    pub penetration: u32,
+    /// How hard whatever is hit gets pushed, relative to a normal bullet.
+    #[serde(default = "one_f")]
+    pub knockback: f32,
+    #[serde(default)]
+    pub splash: Option<Splash>,
+    /// What armor has to resist, `Ballistic` unless given. Splash is always `Explosive`.
+    #[serde(default, deserialize_with = "damage_kind")]
+    pub kind: DamageKind,
+}
+
+fn damage_kind<'de, D>(deserializer: D) -> Result<DamageKind, D::Error>
+    where D: Deserializer<'de> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(|_| D::Error::custom(format!("unknown damage kind {}", name)))
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn one_f() -> f32 {
+    1.0
+}
+
+pub struct BulletDefs {
+    defs: HashMap<String, BulletDef>,
+}
+
+impl BulletDefs {
+    pub fn new() -> Self {
+        BulletDefs { defs: HashMap::new() }
+    }
+
+    pub fn from_toml(filename: &str) -> Result<Self, String> {
+        let defs: HashMap<String, BulletDef> = util::toml::try_toml_value_from_file(filename)?
+            .try_into()
+            .map_err(|e| format!("Bad bullet kind in {}: {}", filename, e))?;
+
+        for (name, def) in defs.iter() {
+            if def.pellets == 0 || def.lifetime <= 0.0 {
+                return Err(format!("Bullet kind {} in {} would never hit anything", name, filename));
+            }
+        }
+
+        Ok(BulletDefs { defs: defs })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BulletDef> {
+        self.defs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.defs.contains_key(name)
+    }
+}
+
+fn bullets_from_toml() -> BulletDefs {
+    match BulletDefs::from_toml(BULLETS_PATH) {
+        Ok(defs) => defs,
+        Err(e) => {
+            log!("Couldn't load bullets: {}", e);
+            BulletDefs::new()
+        },
+    }
+}
+
+make_global!(BULLETS, BulletDefs, bullets_from_toml());
+
+pub use self::instance::*;
+
+/// The stats of a bullet kind, if there is one by that name.
+pub fn def(name: &str) -> Option<BulletDef> {
+    with(|b| b.get(name).cloned())
+}
+
+/// Rereads the bullet kinds. If the file doesn't parse, the error is logged and the old kinds
+/// are kept. Bullets already in flight don't change.
+pub fn reload() {
+    match BulletDefs::from_toml(BULLETS_PATH) {
+        Ok(defs) => with_mut(|b| *b = defs),
+        Err(e) => log!("Couldn't reload bullet kinds: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let defs = BulletDefs::from_toml(BULLETS_PATH).unwrap();
+        let pistol = defs.get("NineMm").unwrap();
+        assert_eq!(pistol.pellets, 1);
+        assert_eq!(pistol.penetration, 0);
+        assert!(pistol.splash.is_none());
+
+        assert!(defs.get("Pellet").unwrap().pellets > 1);
+        assert_eq!(pistol.kind, DamageKind::Ballistic);
+        assert!(defs.get("Rocket").unwrap().splash.is_some());
+        assert_eq!(defs.get("Rocket").unwrap().kind, DamageKind::Explosive);
+        assert!(defs.get("Piercing").unwrap().penetration > 0);
+    }
+}
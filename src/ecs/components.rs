@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use calx_ecs::Entity;
-use rand::{self, Rng};
+use rand::Rng;
 
+use ecs::ballistics::{BulletDef, Splash};
 use ecs::traits::*;
 use point::*;
+use util;
 use world::World;
 
 use ncollide::world::CollisionObjectHandle;
@@ -56,23 +58,34 @@ fn none() -> Option<CollisionObjectHandle> {
     None
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum PhysicsShape {
-    Chara,
-    Wall,
-    Bullet,
+macro_attr! {
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, EnumFromStr!)]
+    pub enum PhysicsShape {
+        Chara,
+        Wall,
+        /// Knee high, so it stops charas walking into it but they can jump onto or over it.
+        Block,
+        Bullet,
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum PhysicsKind {
-    Physical,
-    Bullet,
+macro_attr! {
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, EnumFromStr!)]
+    pub enum PhysicsKind {
+        Physical,
+        /// Never moves, whatever hits it.
+        Static,
+        Bullet,
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Position {
     pub pos: Point,
     pub dir: f32,
+
+    /// Where this entity was at the start of the current tick, for render interpolation.
+    pub last_pos: Point,
 }
 
 impl Position {
@@ -80,9 +93,14 @@ impl Position {
         Position {
             pos: pos,
             dir: 0.0,
+            last_pos: pos,
         }
     }
 
+    pub fn interpolated(&self, alpha: f32) -> Point {
+        self.last_pos + (self.pos - self.last_pos) * alpha
+    }
+
     pub fn cardinal_dir(&self) -> Direction {
         Direction::from_angle(self.dir)
     }
@@ -96,6 +114,15 @@ pub struct Physics {
     pub shape: PhysicsShape,
     pub kind: PhysicsKind,
     pub grounded: bool,
+    /// How hard the body is to push around, both by impulses and by other bodies.
+    #[serde(default = "one")]
+    pub mass: f32,
+    /// How much of its speed into whatever it runs into it keeps, bouncing back. 0 is a dead stop.
+    #[serde(default)]
+    pub restitution: f32,
+    /// How quickly it slows down while nothing pushes it.
+    #[serde(default = "default_friction")]
+    pub friction: f32,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -103,6 +130,16 @@ pub struct Physics {
     pub handle: Option<CollisionObjectHandle>,
 }
 
+pub const DEFAULT_FRICTION: f32 = 7.0;
+
+fn one() -> f32 {
+    1.0
+}
+
+fn default_friction() -> f32 {
+    DEFAULT_FRICTION
+}
+
 impl Physics {
     pub fn new(shape: PhysicsShape, kind: PhysicsKind) -> Self {
         Physics {
@@ -112,9 +149,30 @@ impl Physics {
             shape: shape,
             kind: kind,
             grounded: true,
+            mass: 1.0,
+            restitution: 0.0,
+            friction: DEFAULT_FRICTION,
             handle: None,
         }
     }
+
+    pub fn is_static(&self) -> bool {
+        self.kind == PhysicsKind::Static
+    }
+
+    /// Zero for static bodies, so that anything scaled by it leaves them alone.
+    pub fn inverse_mass(&self) -> f32 {
+        if self.is_static() || self.mass <= 0.0 {
+            0.0
+        } else {
+            1.0 / self.mass
+        }
+    }
+
+    /// Changes the velocity by `impulse`, less the heavier the body is.
+    pub fn apply_impulse(&mut self, impulse: Vector) {
+        self.vel += impulse * self.inverse_mass();
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -168,8 +226,7 @@ impl CharaAppearance {
             face_kind: 5,
         }
     }
-    pub fn new_random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new_random<R: Rng>(rng: &mut R) -> Self {
         CharaAppearance {
             body_kind: rng.next_u32(),
             feet_kind: 0,
@@ -211,8 +268,8 @@ pub enum Appearance {
 }
 
 impl Appearance {
-    pub fn new_chara() -> Self {
-        Appearance::Chara(CharaAppearance::new_random())
+    pub fn new_chara<R: Rng>(rng: &mut R) -> Self {
+        Appearance::Chara(CharaAppearance::new_random(rng))
     }
 
     pub fn new(kind: &str, offset: (i32, i32), variant: u32) -> Self {
@@ -220,56 +277,155 @@ impl Appearance {
     }
 }
 
+macro_attr! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumFromStr!)]
+    pub enum DamageKind {
+        Ballistic,
+        Explosive,
+        Melee,
+        Fire,
+    }
+}
+
+impl Default for DamageKind {
+    fn default() -> Self {
+        DamageKind::Ballistic
+    }
+}
+
+/// Where on a chara something hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HitZone {
+    Head,
+    Body,
+}
+
+/// The fraction of each kind of damage that is stopped, for each hit zone. A helmet is head
+/// armor, a vest is body armor.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Armor {
+    #[serde(default)]
+    pub head: HashMap<DamageKind, f32>,
+    #[serde(default)]
+    pub body: HashMap<DamageKind, f32>,
+}
+
+impl Armor {
+    pub fn resistance(&self, kind: DamageKind, zone: HitZone) -> f32 {
+        let resistances = match zone {
+            HitZone::Head => &self.head,
+            HitZone::Body => &self.body,
+        };
+        resistances.get(&kind).map_or(0.0, |r| util::clamp(*r, 0.0, 1.0))
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bullet {
     pub damage: i32,
     pub time_left: f32,
     pub fired_by: Entity,
+    #[serde(default)]
+    pub kind: DamageKind,
+
+    /// How many more charas it can pass through.
+    #[serde(default)]
+    pub penetration: u32,
+    #[serde(default = "default_knockback")]
+    pub knockback: f32,
+    #[serde(default)]
+    pub splash: Option<Splash>,
+    /// Charas it has already passed through, so they aren't hit again.
+    #[serde(default)]
+    pub hits: Vec<Entity>,
+}
+
+fn default_knockback() -> f32 {
+    1.0
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum BulletKind {
-    NineMm,
+impl Bullet {
+    pub fn new(def: &BulletDef, fired_by: Entity) -> Self {
+        Bullet {
+            damage: def.damage,
+            time_left: def.lifetime,
+            fired_by: fired_by,
+            kind: def.kind,
+
+            penetration: def.penetration,
+            knockback: def.knockback,
+            splash: def.splash,
+            hits: Vec::new(),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Gun {
-    pub bullet: BulletKind,
+    /// The name of a bullet kind from `data/bullets.toml`.
+    pub bullet: String,
     pub spread: f32,
     pub clip_size: u16,
     pub fire_rate_secs: f32,
     pub reload_time_secs: f32,
 
+    /// Rounds in the magazine.
+    pub ammo: u16,
+    /// Rounds left to reload the magazine with.
+    pub reserve_ammo: u16,
+    /// Time until the reload finishes, or 0 when not reloading.
+    pub reload_secs_left: f32,
+
     pub shooting: bool,
     pub secs_to_refire: f32,
 }
 
 impl Gun {
-    pub fn new(bullet: BulletKind, spread: f32, clip_size: u16, fire_rate_ms: u16, reload_time_ms: u16)
-               -> Self {
+    pub fn new(bullet: &str, spread: f32, clip_size: u16, reserve_ammo: u16, fire_rate_ms: u16,
+               reload_time_ms: u16) -> Self {
 
         let fire_rate_secs = fire_rate_ms as f32 / 1000.0;
         Gun {
-            bullet: bullet,
+            bullet: bullet.to_string(),
             spread: spread,
             clip_size: clip_size,
             fire_rate_secs: fire_rate_secs,
             reload_time_secs: reload_time_ms as f32 / 1000.0,
 
+            ammo: clip_size,
+            reserve_ammo: reserve_ammo,
+            reload_secs_left: 0.0,
+
             shooting: false,
             secs_to_refire: 0.0,
         }
     }
 
+    /// Returns how many bullets to fire this frame. Nothing comes out while reloading, and
+    /// emptying the magazine starts a reload.
     pub fn shoot(&mut self, delta: f32) -> u32 {
         self.shooting = true;
 
+        if self.is_reloading() {
+            return 0;
+        }
+        if self.ammo == 0 {
+            self.start_reload();
+            return 0;
+        }
+
         let mut count = 0;
         self.secs_to_refire -= delta;
-        log!("shoot {}", self.secs_to_refire);
         while self.secs_to_refire < 0.0 {
-            count += 1;
             self.secs_to_refire += self.fire_rate_secs;
+            if self.ammo > 0 {
+                self.ammo -= 1;
+                count += 1;
+            }
+        }
+
+        if self.ammo == 0 {
+            self.start_reload();
         }
         count
     }
@@ -277,43 +433,299 @@ impl Gun {
     pub fn reset_refire(&mut self) {
         self.secs_to_refire = 0.0;
     }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_secs_left > 0.0
+    }
+
+    pub fn can_reload(&self) -> bool {
+        !self.is_reloading() && self.ammo < self.clip_size && self.reserve_ammo > 0
+    }
+
+    /// Starts reloading if the magazine isn't full and there is ammo to fill it with.
+    pub fn start_reload(&mut self) -> bool {
+        if !self.can_reload() {
+            return false;
+        }
+        // Zero-length reloads still have to go through `step_reload` to refill.
+        self.reload_secs_left = self.reload_time_secs.max(::std::f32::EPSILON);
+        true
+    }
+
+    /// Counts down a reload in progress, and refills the magazine once it is done.
+    pub fn step_reload(&mut self, delta: f32) {
+        if !self.is_reloading() {
+            return;
+        }
+
+        self.reload_secs_left -= delta;
+        if self.reload_secs_left <= 0.0 {
+            self.reload_secs_left = 0.0;
+            let loaded = (self.clip_size - self.ammo).min(self.reserve_ammo);
+            self.ammo += loaded;
+            self.reserve_ammo -= loaded;
+        }
+    }
+
+    /// Whether pulling the trigger would fire, now or once the refire delay is over.
+    pub fn has_ammo(&self) -> bool {
+        !self.is_reloading() && self.ammo > 0
+    }
 }
 
+/// A close-range attack that hits every chara in an arc in front of the attacker.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Team(u8);
+pub struct Melee {
+    pub damage: i32,
+    pub range: f32,
+    /// Width of the arc in radians, centered on the way the attacker faces.
+    pub arc: f32,
+    pub knockback: f32,
+    pub cooldown_secs: f32,
+
+    pub secs_to_ready: f32,
+}
+
+impl Melee {
+    pub fn new(damage: i32, range: f32, arc: f32, knockback: f32, cooldown_ms: u16) -> Self {
+        Melee {
+            damage: damage,
+            range: range,
+            arc: arc,
+            knockback: knockback,
+            cooldown_secs: cooldown_ms as f32 / 1000.0,
+
+            secs_to_ready: 0.0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.secs_to_ready <= 0.0
+    }
+
+    /// Starts the cooldown if the attack is ready. Returns whether it was.
+    pub fn swing(&mut self) -> bool {
+        if !self.is_ready() {
+            return false;
+        }
+        self.secs_to_ready = self.cooldown_secs;
+        true
+    }
+
+    pub fn step(&mut self, delta: f32) {
+        self.secs_to_ready = (self.secs_to_ready - delta).max(0.0);
+    }
+}
+
+/// The name of a faction from `data/factions.toml`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Team(pub String);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Chara {
-    team: Team
+    /// Charas without a team aren't hostile to anyone, and nobody is hostile to them.
+    #[serde(default)]
+    pub team: Option<Team>,
 }
 
 impl Chara {
-    pub fn new() -> Self {
+    pub fn new(team: &str) -> Self {
         Chara {
-            team: Team(0),
+            team: Some(Team(team.to_string())),
         }
     }
+
+    pub fn without_team() -> Self {
+        Chara {
+            team: None,
+        }
+    }
+
+    pub fn team(&self) -> Option<&str> {
+        self.team.as_ref().map(|t| t.0.as_str())
+    }
 }
 
-// entity, is_holder
+// entity, is_holder. Ordered, so the first gun held and the order things are dropped in are the
+// same in every run with the same seed, which replays rely on.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Holds(pub HashMap<Entity, bool>);
+pub struct Holds(pub BTreeMap<Entity, bool>);
 
 impl Holds {
     pub fn new() -> Self {
-        Holds(HashMap::new())
+        Holds(BTreeMap::new())
+    }
+}
+
+/// The weapons a chara carries, one of which is in hand. Everything in it is also in the chara's
+/// `Holds`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    pub slots: usize,
+    pub weapons: Vec<Entity>,
+    /// Index of the weapon in hand.
+    pub current: usize,
+}
+
+impl Inventory {
+    pub fn new(slots: usize) -> Self {
+        Inventory {
+            slots: slots,
+            weapons: Vec::new(),
+            current: 0,
+        }
+    }
+
+    pub fn wielded(&self) -> Option<Entity> {
+        self.weapons.get(self.current).cloned()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.weapons.len() >= self.slots
+    }
+
+    pub fn contains(&self, item: Entity) -> bool {
+        self.weapons.contains(&item)
+    }
+
+    /// Puts `item` in a free slot and takes it in hand. Returns false if there is no room.
+    pub fn add(&mut self, item: Entity) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        if !self.contains(item) {
+            self.weapons.push(item);
+        }
+        self.current = self.weapons.iter().position(|w| *w == item).unwrap();
+        true
+    }
+
+    pub fn remove(&mut self, item: Entity) {
+        if let Some(index) = self.weapons.iter().position(|w| *w == item) {
+            self.weapons.remove(index);
+            if index < self.current || self.current >= self.weapons.len() {
+                self.current = self.current.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Switches `step` slots forward, or backward if negative, wrapping around.
+    pub fn cycle(&mut self, step: i32) {
+        let len = self.weapons.len() as i32;
+        if len > 0 {
+            self.current = ((self.current as i32 + step) % len + len) as usize % len as usize;
+        }
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bomb {
     pub time_left: f32,
+    /// Whoever set the bomb, for deciding who the blast can hurt.
+    #[serde(default)]
+    pub owner: Option<Entity>,
+    /// Damage dealt to everything with health around the bomb when it goes off.
+    #[serde(default = "bomb_blast")]
+    pub blast: Splash,
+}
+
+fn bomb_blast() -> Splash {
+    Splash { radius: 4.0, damage: 100 }
 }
 
 impl Bomb {
     pub fn new() -> Self {
         Bomb {
             time_left: 2.0,
+            owner: None,
+            blast: bomb_blast(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::Ecs;
+
+    #[test]
+    fn test_gun_reload() {
+        let mut gun = Gun::new("NineMm", 0.0, 3, 4, 100, 500);
+        assert_eq!(gun.shoot(0.25), 3);
+        assert_eq!(gun.ammo, 0);
+        assert!(gun.is_reloading());
+        assert_eq!(gun.shoot(1.0), 0);
+
+        gun.step_reload(0.5);
+        assert!(!gun.is_reloading());
+        assert_eq!((gun.ammo, gun.reserve_ammo), (3, 1));
+
+        gun.reset_refire();
+        assert_eq!(gun.shoot(0.01), 1);
+        assert!(gun.start_reload());
+        gun.step_reload(0.5);
+        assert_eq!((gun.ammo, gun.reserve_ammo), (3, 0));
+        assert!(!gun.can_reload());
+    }
+
+    #[test]
+    fn test_impulse_by_mass() {
+        let mut light = Physics::new(PhysicsShape::Block, PhysicsKind::Physical);
+        light.mass = 0.5;
+        let mut heavy = Physics::new(PhysicsShape::Chara, PhysicsKind::Physical);
+        heavy.mass = 2.0;
+        let mut wall = Physics::new(PhysicsShape::Wall, PhysicsKind::Static);
+
+        let push = Vector::new(1.0, 0.0, 0.0);
+        light.apply_impulse(push);
+        heavy.apply_impulse(push);
+        wall.apply_impulse(push);
+        assert_eq!(light.vel.x, 2.0);
+        assert_eq!(heavy.vel.x, 0.5);
+        assert_eq!(wall.vel.x, 0.0);
+    }
+
+    #[test]
+    fn test_armor() {
+        let mut armor = Armor::default();
+        armor.head.insert(DamageKind::Ballistic, 0.5);
+        armor.body.insert(DamageKind::Explosive, 2.0);
+        assert_eq!(armor.resistance(DamageKind::Ballistic, HitZone::Head), 0.5);
+        assert_eq!(armor.resistance(DamageKind::Ballistic, HitZone::Body), 0.0);
+        assert_eq!(armor.resistance(DamageKind::Explosive, HitZone::Body), 1.0);
+    }
+
+    #[test]
+    fn test_melee_cooldown() {
+        let mut melee = Melee::new(10, 1.0, 1.0, 1.0, 500);
+        assert!(melee.swing());
+        assert!(!melee.swing());
+        melee.step(0.25);
+        assert!(!melee.is_ready());
+        melee.step(0.25);
+        assert!(melee.swing());
+    }
+
+    #[test]
+    fn test_inventory() {
+        let mut ecs = Ecs::new();
+        let (a, b, c) = (ecs.make(), ecs.make(), ecs.make());
+
+        let mut inventory = Inventory::new(2);
+        assert!(inventory.add(a));
+        assert!(inventory.add(b));
+        assert!(!inventory.add(c));
+        assert_eq!(inventory.wielded(), Some(b));
+
+        inventory.cycle(1);
+        assert_eq!(inventory.wielded(), Some(a));
+        inventory.cycle(-1);
+        assert_eq!(inventory.wielded(), Some(b));
+
+        inventory.remove(b);
+        assert_eq!(inventory.wielded(), Some(a));
+        inventory.remove(a);
+        assert_eq!(inventory.wielded(), None);
+        inventory.cycle(1);
+    }
+}
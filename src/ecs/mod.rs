@@ -1,5 +1,7 @@
+pub mod ballistics;
 pub mod components;
 pub mod prefab;
+pub mod query;
 pub mod traits;
 use ai;
 use point;
@@ -7,6 +9,7 @@ use point;
 Ecs! {
     positions: components::Position,
     healths: components::Health,
+    armors: components::Armor,
     names: components::Name,
     physics: components::Physics,
     ais: ai::Ai,
@@ -15,6 +18,8 @@ Ecs! {
     appearances: components::Appearance,
     bullets: components::Bullet,
     guns: components::Gun,
+    melees: components::Melee,
     holds: components::Holds,
+    inventories: components::Inventory,
     bombs: components::Bomb,
 }
@@ -1,53 +1,425 @@
+use std::collections::HashMap;
+
 use calx_ecs::Entity;
-use debug;
+use glob;
+use rand::Rng;
+use toml::Value;
+use toml::value::Table;
+
+use ai::{Ai, AiKind};
 use ecs::Loadout;
+use ecs::ballistics::{self, BulletDef, Splash};
 use ecs::components::*;
+use util;
+use world::faction;
 
-pub fn mob(name: &str) -> Loadout {
-    Loadout::new()
-        .c(Appearance::new_chara())
-        .c(Name::new(name))
-        .c(Health::new(debug::get("health") as i32))
-        .c(Physics::new(PhysicsShape::Chara, PhysicsKind::Physical))
-        .c(Chara::new())
-}
-
-pub fn gun() -> Loadout {
-    let mut app = Appearance::Object(ObjectAppearance {
-        kind: "gun".to_string(),
-        offset: (0, 0),
-        variant: 5,
-        directional: true
-    }
-    );
-    let gun = Gun::new(
-        BulletKind::NineMm,
-        debug::get("spread"),
-        debug::get("clip_size") as u16,
-        debug::get("fire_rate") as u16,
-        debug::get("reload_time") as u16,
-    );
-    Loadout::new()
-        .c(app)
-        .c(gun)
+pub const PREFABS_PATH: &str = "data/prefabs";
+
+#[derive(Debug)]
+pub enum PrefabError {
+    NoSuchPrefab(String),
+    InheritanceCycle(String),
+    EquipCycle(String),
+    UnknownComponent(String, String),
+    BadComponent(String, String),
+    BadRange(i32, i32),
 }
 
-pub fn bomb() -> Loadout {
-    Loadout::new()
-        .c(Appearance::new("wall", (0, -70), 0))
-        .c(Bomb::new())
+pub type PrefabResult<T> = Result<T, PrefabError>;
+
+/// Named component lists read from `data/prefabs/*.toml`.
+///
+/// Each top-level table in those files is a prefab. A prefab with a `parent` key starts out with
+/// everything its parent has, and its own keys replace the parent's; tables like `gun` are merged
+/// key by key, so a child can change one stat and keep the rest.
+pub struct Prefabs {
+    defs: HashMap<String, Table>,
+}
+
+impl Prefabs {
+    pub fn new() -> Self {
+        Prefabs { defs: HashMap::new() }
+    }
+
+    pub fn from_dir(dir: &str) -> Self {
+        Prefabs::try_from_dir(dir).unwrap()
+    }
+
+    /// Reads every file in `dir`, failing on the first one that isn't valid TOML, or if prefabs
+    /// equip each other in a circle.
+    pub fn try_from_dir(dir: &str) -> Result<Self, String> {
+        let mut prefabs = Prefabs::new();
+        for entry in glob::glob(&format!("{}/*.toml", dir)).unwrap() {
+            if let Ok(path) = entry {
+                let filename = path.to_string_lossy().into_owned();
+                prefabs.add_from_value(&util::toml::try_toml_value_from_file(&filename)?, &filename);
+            }
+        }
+        prefabs.check_equips().map_err(|e| format!("Bad prefabs in {}: {:?}", dir, e))?;
+        Ok(prefabs)
+    }
+
+    /// Makes sure no prefab ends up equipping itself, which would spawn things forever.
+    pub fn check_equips(&self) -> PrefabResult<()> {
+        for name in self.defs.keys() {
+            let mut chain = vec![name.clone()];
+            let mut next = self.equip(name);
+            while let Some(current) = next {
+                if chain.contains(&current) {
+                    return Err(PrefabError::EquipCycle(name.clone()));
+                }
+                next = self.equip(&current);
+                chain.push(current);
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of the prefab to spawn and equip alongside this one, if any.
+    pub fn equip(&self, name: &str) -> Option<String> {
+        self.resolve(name).ok()
+            .and_then(|table| table.get("equip").and_then(|e| e.as_str()).map(|e| e.to_string()))
+    }
+
+    pub fn add_from_value(&mut self, value: &Value, filename: &str) {
+        let table = match *value {
+            Value::Table(ref table) => table,
+            _ => return,
+        };
+
+        for (name, def) in table.iter() {
+            match *def {
+                Value::Table(ref def) => {
+                    if self.defs.insert(name.clone(), def.clone()).is_some() {
+                        log!("Prefab {} in {} replaces an earlier one", name, filename);
+                    }
+                },
+                _ => {
+                    log!("Prefab {} in {} is not a table", name, filename);
+                },
+            }
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.defs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the prefab's components with everything inherited from its parents filled in.
+    pub fn resolve(&self, name: &str) -> PrefabResult<Table> {
+        let mut chain = Vec::new();
+        let mut next = Some(name.to_string());
+        while let Some(current) = next {
+            if chain.contains(&current) {
+                return Err(PrefabError::InheritanceCycle(name.to_string()));
+            }
+
+            let def = match self.defs.get(&current) {
+                Some(def) => def,
+                None => return Err(PrefabError::NoSuchPrefab(current)),
+            };
+            next = def.get("parent").and_then(|p| p.as_str()).map(|p| p.to_string());
+            chain.push(current);
+        }
+
+        let mut table = Table::new();
+        for current in chain.iter().rev() {
+            merge(&mut table, &self.defs[current]);
+        }
+        table.remove("parent");
+        Ok(table)
+    }
+
+    pub fn loadout<R: Rng>(&self, name: &str, rng: &mut R) -> PrefabResult<Loadout> {
+        let table = self.resolve(name)?;
+        let mut loadout = Loadout::new();
+        for (key, value) in table.iter() {
+            loadout = add_component(loadout, name, key, value, rng)?;
+        }
+        Ok(loadout)
+    }
+}
+
+fn merge(into: &mut Table, from: &Table) {
+    for (key, value) in from.iter() {
+        let merged = match (into.get_mut(key), value) {
+            (Some(&mut Value::Table(ref mut old)), &Value::Table(ref new)) => {
+                merge(old, new);
+                true
+            },
+            _ => false,
+        };
+
+        if !merged {
+            into.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PhysicsDef {
+    shape: String,
+    kind: String,
+    #[serde(default)]
+    mass: Option<f32>,
+    #[serde(default)]
+    restitution: Option<f32>,
+    #[serde(default)]
+    friction: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct ObjectAppearanceDef {
+    kind: String,
+    #[serde(default)]
+    offset: (i32, i32),
+    #[serde(default)]
+    variant: u32,
+    #[serde(default)]
+    directional: bool,
+}
+
+#[derive(Deserialize)]
+struct GunDef {
+    bullet: String,
+    spread: f32,
+    clip_size: u16,
+    reserve_ammo: u16,
+    fire_rate_ms: u16,
+    reload_time_ms: u16,
 }
 
-pub fn bullet(fired_by: Entity) -> Loadout {
+#[derive(Deserialize)]
+struct MeleeDef {
+    damage: i32,
+    range: f32,
+    arc: f32,
+    knockback: f32,
+    cooldown_ms: u16,
+}
+
+#[derive(Deserialize)]
+struct ArmorDef {
+    #[serde(default)]
+    head: HashMap<String, f32>,
+    #[serde(default)]
+    body: HashMap<String, f32>,
+}
+
+fn resistances(def: &HashMap<String, f32>) -> Option<HashMap<DamageKind, f32>> {
+    let mut resistances = HashMap::new();
+    for (name, resistance) in def.iter() {
+        resistances.insert(name.parse::<DamageKind>().ok()?, *resistance);
+    }
+    Some(resistances)
+}
+
+#[derive(Deserialize)]
+struct BombDef {
+    time_left: f32,
+    blast: Splash,
+}
+
+#[derive(Deserialize)]
+struct CharaDef {
+    team: String,
+}
+
+fn add_component<R: Rng>(loadout: Loadout, prefab: &str, key: &str, value: &Value, rng: &mut R)
+                         -> PrefabResult<Loadout> {
+    let bad = || PrefabError::BadComponent(prefab.to_string(), key.to_string());
+
+    let loadout = match key {
+        // Handled by `World::spawn_prefab`.
+        "equip" => loadout,
+
+        "name" => loadout.c(Name::new(value.as_str().ok_or(bad())?)),
+        "health" => {
+            let hp = value.as_integer().ok_or(bad())?;
+            if hp <= 0 {
+                return Err(bad());
+            }
+            loadout.c(Health::new(hp as i32))
+        },
+        "inventory" => {
+            let slots = value.as_integer().ok_or(bad())?;
+            if slots <= 0 {
+                return Err(bad());
+            }
+            loadout.c(Inventory::new(slots as usize))
+        },
+        "chara" => match *value {
+            Value::Boolean(true) => loadout.c(Chara::without_team()),
+            Value::Table(_) => {
+                let def: CharaDef = value.clone().try_into().map_err(|_| bad())?;
+                if !faction::with(|f| f.contains(&def.team)) {
+                    return Err(bad());
+                }
+                loadout.c(Chara::new(&def.team))
+            },
+            _ => return Err(bad()),
+        },
+        "ai" => {
+            let kind = value.as_str().and_then(|s| s.parse::<AiKind>().ok()).ok_or(bad())?;
+            loadout.c(Ai::new(kind))
+        },
+        "physics" => {
+            let def: PhysicsDef = value.clone().try_into().map_err(|_| bad())?;
+            let shape = def.shape.parse::<PhysicsShape>().map_err(|_| bad())?;
+            let kind = def.kind.parse::<PhysicsKind>().map_err(|_| bad())?;
+            let mut physics = Physics::new(shape, kind);
+            physics.mass = def.mass.unwrap_or(physics.mass);
+            physics.restitution = def.restitution.unwrap_or(physics.restitution);
+            physics.friction = def.friction.unwrap_or(physics.friction);
+            if physics.mass <= 0.0 {
+                return Err(bad());
+            }
+            loadout.c(physics)
+        },
+        "appearance" => match *value {
+            Value::String(ref s) if s == "chara" => loadout.c(Appearance::new_chara(rng)),
+            Value::Table(_) => {
+                let def: ObjectAppearanceDef = value.clone().try_into().map_err(|_| bad())?;
+                loadout.c(Appearance::Object(ObjectAppearance {
+                    kind: def.kind,
+                    offset: def.offset,
+                    variant: def.variant,
+                    directional: def.directional,
+                }))
+            },
+            _ => return Err(bad()),
+        },
+        "gun" => {
+            let def: GunDef = value.clone().try_into().map_err(|_| bad())?;
+            if !ballistics::with(|b| b.contains(&def.bullet)) {
+                return Err(bad());
+            }
+            loadout.c(Gun::new(&def.bullet, def.spread, def.clip_size, def.reserve_ammo, def.fire_rate_ms,
+                               def.reload_time_ms))
+        },
+        "melee" => {
+            let def: MeleeDef = value.clone().try_into().map_err(|_| bad())?;
+            if def.range <= 0.0 || def.arc <= 0.0 {
+                return Err(bad());
+            }
+            loadout.c(Melee::new(def.damage, def.range, def.arc, def.knockback, def.cooldown_ms))
+        },
+        "armor" => {
+            let def: ArmorDef = value.clone().try_into().map_err(|_| bad())?;
+            loadout.c(Armor {
+                head: resistances(&def.head).ok_or(bad())?,
+                body: resistances(&def.body).ok_or(bad())?,
+            })
+        },
+        "bomb" => {
+            let def: BombDef = value.clone().try_into().map_err(|_| bad())?;
+            loadout.c(Bomb { time_left: def.time_left, owner: None, blast: def.blast })
+        },
+        _ => return Err(PrefabError::UnknownComponent(prefab.to_string(), key.to_string())),
+    };
+
+    Ok(loadout)
+}
+
+make_global!(PREFABS, Prefabs, Prefabs::from_dir(PREFABS_PATH));
+
+pub use self::instance::*;
+
+/// Rereads the prefab files. If any of them doesn't parse, the error is logged and the old
+/// prefabs are kept. Entities that were already spawned don't change.
+pub fn reload() {
+    match Prefabs::try_from_dir(PREFABS_PATH) {
+        Ok(prefabs) => with_mut(|p| *p = prefabs),
+        Err(e) => log!("Couldn't reload prefabs: {}", e),
+    }
+}
+
+/// The name of the prefab to spawn and equip alongside this one, if any.
+pub fn equipment(name: &str) -> Option<String> {
+    with(|p| p.equip(name))
+}
+
+pub fn bullet(def: &BulletDef, fired_by: Entity) -> Loadout {
     Loadout::new()
         .c(Appearance::Bullet)
         .c(Physics::new(PhysicsShape::Bullet, PhysicsKind::Bullet))
-        .c(Bullet { damage: debug::get("bullet_damage") as i32, time_left: debug::get("bullet_time"), fired_by: fired_by })
+        .c(Bullet::new(def, fired_by))
 }
 
-pub fn wall() -> Loadout {
-    Loadout::new()
-        .c(Appearance::new("wall", (0, -70), 0))
-        .c(Health::new(100))
-        .c(Physics::new(PhysicsShape::Wall, PhysicsKind::Physical))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, XorShiftRng};
+
+    fn prefabs(data: &str) -> Prefabs {
+        let mut prefabs = Prefabs::new();
+        prefabs.add_from_value(&util::toml::toml_value_from_string(data), "test");
+        prefabs
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let prefabs = prefabs("
+[gun]
+gun = { bullet = \"NineMm\", spread = 0.1, clip_size = 60, reserve_ammo = 120, fire_rate_ms = 100, reload_time_ms = 10 }
+
+[smg]
+parent = \"gun\"
+gun = { fire_rate_ms = 50 }
+");
+        let smg = prefabs.resolve("smg").unwrap();
+        let gun = smg["gun"].as_table().unwrap();
+        assert_eq!(gun["fire_rate_ms"].as_integer(), Some(50));
+        assert_eq!(gun["clip_size"].as_integer(), Some(60));
+        assert!(smg.get("parent").is_none());
+
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        assert!(prefabs.loadout("smg", &mut rng).is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        let prefabs = prefabs("
+[a]
+parent = \"b\"
+
+[b]
+parent = \"a\"
+
+[c]
+health = \"lots\"
+");
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        assert!(match prefabs.resolve("a") {
+            Err(PrefabError::InheritanceCycle(_)) => true,
+            _ => false,
+        });
+        assert!(match prefabs.loadout("c", &mut rng) {
+            Err(PrefabError::BadComponent(_, _)) => true,
+            _ => false,
+        });
+        assert!(prefabs.resolve("d").is_err());
+    }
+
+    #[test]
+    fn test_equip_cycle() {
+        let cycle = prefabs("
+[a]
+equip = \"b\"
+
+[b]
+equip = \"c\"
+
+[c]
+equip = \"a\"
+");
+        assert!(match cycle.check_equips() {
+            Err(PrefabError::EquipCycle(_)) => true,
+            _ => false,
+        });
+        assert!(prefabs("[a]\nequip = \"a\"").check_equips().is_err());
+        assert!(prefabs("[a]\nequip = \"b\"\n[b]\nhealth = 1").check_equips().is_ok());
+    }
 }
@@ -0,0 +1,161 @@
+//! Joins over component storages.
+//!
+//! Instead of walking every entity in the world and checking each storage, a join walks the
+//! entities of its first storage and skips the ones missing any of the others. Put the storage
+//! with the fewest entries first.
+//!
+//! ```ignore
+//! for (entity, pos, phys) in query::join2(&ecs.positions, &ecs.physics) {
+//!     ...
+//! }
+//!
+//! query::each_mut2(&mut ecs.physics, &mut ecs.positions, |entity, phys, pos| {
+//!     ...
+//! });
+//! ```
+
+use std::slice;
+
+use calx_ecs::{ComponentData, Entity};
+
+use ecs::Component;
+
+/// Iterates over the entities of a single storage along with their components.
+pub struct Iter<'a, A: 'a> {
+    entities: slice::Iter<'a, Entity>,
+    a: &'a ComponentData<A>,
+}
+
+impl<'a, A: Component> Iterator for Iter<'a, A> {
+    type Item = (Entity, &'a A);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&e) = self.entities.next() {
+            if let Some(a) = self.a.get(e) {
+                return Some((e, a));
+            }
+        }
+        None
+    }
+}
+
+pub fn iter<'a, A: Component>(a: &'a ComponentData<A>) -> Iter<'a, A> {
+    Iter {
+        entities: a.ent_iter(),
+        a: a,
+    }
+}
+
+/// Iterates over the entities that have both components.
+pub struct Join2<'a, A: 'a, B: 'a> {
+    entities: slice::Iter<'a, Entity>,
+    a: &'a ComponentData<A>,
+    b: &'a ComponentData<B>,
+}
+
+impl<'a, A: Component, B: Component> Iterator for Join2<'a, A, B> {
+    type Item = (Entity, &'a A, &'a B);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&e) = self.entities.next() {
+            if let (Some(a), Some(b)) = (self.a.get(e), self.b.get(e)) {
+                return Some((e, a, b));
+            }
+        }
+        None
+    }
+}
+
+pub fn join2<'a, A, B>(a: &'a ComponentData<A>, b: &'a ComponentData<B>) -> Join2<'a, A, B>
+    where A: Component, B: Component {
+    Join2 {
+        entities: a.ent_iter(),
+        a: a,
+        b: b,
+    }
+}
+
+/// Iterates over the entities that have all three components.
+pub struct Join3<'a, A: 'a, B: 'a, C: 'a> {
+    entities: slice::Iter<'a, Entity>,
+    a: &'a ComponentData<A>,
+    b: &'a ComponentData<B>,
+    c: &'a ComponentData<C>,
+}
+
+impl<'a, A: Component, B: Component, C: Component> Iterator for Join3<'a, A, B, C> {
+    type Item = (Entity, &'a A, &'a B, &'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&e) = self.entities.next() {
+            if let (Some(a), Some(b), Some(c)) = (self.a.get(e), self.b.get(e), self.c.get(e)) {
+                return Some((e, a, b, c));
+            }
+        }
+        None
+    }
+}
+
+pub fn join3<'a, A, B, C>(a: &'a ComponentData<A>,
+                          b: &'a ComponentData<B>,
+                          c: &'a ComponentData<C>)
+                          -> Join3<'a, A, B, C>
+    where A: Component, B: Component, C: Component {
+    Join3 {
+        entities: a.ent_iter(),
+        a: a,
+        b: b,
+        c: c,
+    }
+}
+
+// A mutable iterator can't hand out references into a storage it is still walking, so the mutable
+// joins take a callback. The entity ids of the first storage are copied up front; that is the only
+// allocation.
+
+/// Runs `f` on every component in the storage.
+pub fn each_mut<A, F>(a: &mut ComponentData<A>, mut f: F)
+    where A: Component, F: FnMut(Entity, &mut A) {
+    let entities: Vec<Entity> = a.ent_iter().cloned().collect();
+    for e in entities {
+        if let Some(a) = a.get_mut(e) {
+            f(e, a);
+        }
+    }
+}
+
+/// Runs `f` on every entity that has both components.
+pub fn each_mut2<A, B, F>(a: &mut ComponentData<A>, b: &mut ComponentData<B>, mut f: F)
+    where A: Component, B: Component, F: FnMut(Entity, &mut A, &mut B) {
+    let entities: Vec<Entity> = a.ent_iter().cloned().collect();
+    for e in entities {
+        if let (Some(a), Some(b)) = (a.get_mut(e), b.get_mut(e)) {
+            f(e, a, b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::Ecs;
+    use ecs::components::*;
+    use point;
+
+    #[test]
+    fn test_join() {
+        let mut ecs = Ecs::new();
+        let a = ecs.make();
+        let b = ecs.make();
+        ecs.positions.insert(a, Position::new(point::zero()));
+        ecs.positions.insert(b, Position::new(point::zero()));
+        ecs.healths.insert(b, Health::new(10));
+
+        let joined: Vec<Entity> = join2(&ecs.healths, &ecs.positions).map(|(e, _, _)| e).collect();
+        assert_eq!(joined, vec![b]);
+
+        each_mut2(&mut ecs.positions, &mut ecs.healths, |_, pos, _| pos.pos.x = 1.0);
+        assert_eq!(ecs.positions.get(a).unwrap().pos.x, 0.0);
+        assert_eq!(ecs.positions.get(b).unwrap().pos.x, 1.0);
+    }
+}
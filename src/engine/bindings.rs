@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+
+use toml::{self, Value};
+
+use engine::MouseState;
+use engine::keys::{Key, KeyCode};
+use util;
+
+pub const BINDINGS_PATH: &str = "data/bindings.toml";
+
+macro_attr! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd, EnumFromStr!)]
+    pub enum InputAction {
+        MoveUp,
+        MoveDown,
+        MoveLeft,
+        MoveRight,
+        Jump,
+        Shoot,
+        Reload,
+        Melee,
+        PickUp,
+        DropWeapon,
+        NextWeapon,
+        PreviousWeapon,
+        Bom,
+        RotateCameraLeft,
+        RotateCameraRight,
+        ReloadShaders,
+        Restart,
+        QuickSave,
+        QuickLoad,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl Trigger {
+    /// Parses a trigger like "W", "ctrl+shift+F5" or "MouseLeft".
+    pub fn parse(s: &str) -> Option<Trigger> {
+        let parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let (name, modifiers) = match parts.split_last() {
+            Some(split) => split,
+            None => return None,
+        };
+
+        let mut key = Key::default();
+        for modifier in modifiers.iter() {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => key.ctrl = true,
+                "alt" => key.alt = true,
+                "shift" => key.shift = true,
+                _ => return None,
+            }
+        }
+
+        let button = match name.to_lowercase().as_str() {
+            "mouseleft" => Some(MouseButton::Left),
+            "mouseright" => Some(MouseButton::Right),
+            "mousemiddle" => Some(MouseButton::Middle),
+            _ => None,
+        };
+
+        match button {
+            Some(button) => if modifiers.is_empty() {
+                Some(Trigger::Mouse(button))
+            } else {
+                None
+            },
+            None => KeyCode::from_name(name).map(|code| {
+                key.code = code;
+                Trigger::Key(key)
+            }),
+        }
+    }
+
+    fn modifier_count(&self) -> usize {
+        match *self {
+            Trigger::Key(key) => key.ctrl as usize + key.alt as usize + key.shift as usize,
+            Trigger::Mouse(_) => 0,
+        }
+    }
+
+    fn is_held(&self, input: &HashMap<KeyCode, bool>, mouse: &MouseState) -> bool {
+        let held = |code: KeyCode| input.get(&code).map_or(false, |b| *b);
+        match *self {
+            Trigger::Key(key) => {
+                held(key.code) &&
+                    (!key.ctrl || held(KeyCode::Ctrl)) &&
+                    (!key.alt || held(KeyCode::Alt)) &&
+                    (!key.shift || held(KeyCode::Shift))
+            },
+            Trigger::Mouse(MouseButton::Left) => mouse.pressed.0,
+            Trigger::Mouse(MouseButton::Right) => mouse.pressed.1,
+            Trigger::Mouse(MouseButton::Middle) => mouse.pressed.2,
+        }
+    }
+
+    /// A held chord like "ctrl+S" hides a plain "S" binding, so both don't fire at once.
+    fn is_shadowed_by(&self, other: &Trigger) -> bool {
+        match (*self, *other) {
+            (Trigger::Key(a), Trigger::Key(b)) => {
+                a.code == b.code && other.modifier_count() > self.modifier_count()
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Trigger::Key(key) => {
+                if key.ctrl {
+                    write!(f, "ctrl+")?;
+                }
+                if key.alt {
+                    write!(f, "alt+")?;
+                }
+                if key.shift {
+                    write!(f, "shift+")?;
+                }
+                write!(f, "{:?}", key.code)
+            },
+            Trigger::Mouse(button) => write!(f, "Mouse{:?}", button),
+        }
+    }
+}
+
+pub struct Bindings {
+    bindings: HashMap<InputAction, Vec<Trigger>>,
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Bindings { bindings: HashMap::new() }
+    }
+
+    /// Reads the bindings file. Bad entries are logged and skipped instead of panicking, so one
+    /// typo doesn't leave the player with no controls.
+    pub fn from_toml(filename: &str) -> Self {
+        Bindings::from_value(&util::toml::toml_value_from_file(filename), filename)
+    }
+
+    pub fn from_value(value: &Value, filename: &str) -> Self {
+        let mut bindings = Bindings::new();
+
+        let table = match util::toml::get_value_in_table(value, "bindings") {
+            Some(&Value::Table(ref table)) => table,
+            _ => {
+                log!("No [bindings] table found in {}!", filename);
+                return bindings;
+            },
+        };
+
+        for (action_name, triggers) in table.iter() {
+            let action = match action_name.parse::<InputAction>() {
+                Ok(a) => a,
+                Err(_) => {
+                    log!("Unknown input action {} in {}", action_name, filename);
+                    continue;
+                },
+            };
+
+            let names: Vec<String> = match triggers.clone().try_into() {
+                Ok(n) => n,
+                Err(_) => {
+                    log!("Bindings for {} must be an array of strings", action_name);
+                    continue;
+                },
+            };
+
+            for name in names.iter() {
+                match Trigger::parse(name) {
+                    Some(trigger) => bindings.bind(action, trigger),
+                    None => {
+                        log!("Invalid binding \"{}\" for {}", name, action_name);
+                    },
+                }
+            }
+        }
+
+        for (trigger, actions) in bindings.conflicts() {
+            log!("Conflicting bindings: {} is bound to {:?}", trigger, actions);
+        }
+
+        bindings
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut table = BTreeMap::new();
+        for (action, triggers) in self.bindings.iter() {
+            let names = triggers.iter().map(|t| Value::String(t.to_string())).collect();
+            table.insert(format!("{:?}", action), Value::Array(names));
+        }
+
+        let mut root = BTreeMap::new();
+        root.insert("bindings".to_string(), Value::Table(table));
+
+        let data = toml::to_string(&Value::Table(root)).unwrap();
+        let mut file = File::create(filename)?;
+        file.write_all(data.as_bytes())
+    }
+
+    pub fn bind(&mut self, action: InputAction, trigger: Trigger) {
+        let triggers = self.bindings.entry(action).or_insert(Vec::new());
+        if !triggers.contains(&trigger) {
+            triggers.push(trigger);
+        }
+    }
+
+    pub fn unbind(&mut self, trigger: Trigger) {
+        for triggers in self.bindings.values_mut() {
+            triggers.retain(|t| *t != trigger);
+        }
+    }
+
+    pub fn unbind_action(&mut self, action: InputAction) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn triggers(&self, action: InputAction) -> &[Trigger] {
+        self.bindings.get(&action).map_or(&[][..], |t| t.as_slice())
+    }
+
+    /// Returns every trigger that is bound to more than one action.
+    pub fn conflicts(&self) -> Vec<(Trigger, Vec<InputAction>)> {
+        let mut by_trigger: HashMap<Trigger, Vec<InputAction>> = HashMap::new();
+        for (action, triggers) in self.bindings.iter() {
+            for trigger in triggers.iter() {
+                by_trigger.entry(*trigger).or_insert(Vec::new()).push(*action);
+            }
+        }
+
+        let mut conflicts: Vec<(Trigger, Vec<InputAction>)> =
+            by_trigger.into_iter()
+                      .filter(|&(_, ref actions)| actions.len() > 1)
+                      .map(|(trigger, mut actions)| {
+                          actions.sort();
+                          (trigger, actions)
+                      })
+                      .collect();
+        conflicts.sort_by_key(|&(_, ref actions)| actions[0]);
+        conflicts
+    }
+
+    pub fn active_actions(&self, input: &HashMap<KeyCode, bool>, mouse: &MouseState) -> HashSet<InputAction> {
+        let mut held = Vec::new();
+        for (action, triggers) in self.bindings.iter() {
+            for trigger in triggers.iter() {
+                if trigger.is_held(input, mouse) {
+                    held.push((*action, *trigger));
+                }
+            }
+        }
+
+        held.iter()
+            .filter(|&&(_, trigger)| !held.iter().any(|&(_, other)| trigger.is_shadowed_by(&other)))
+            .map(|&(action, _)| action)
+            .collect()
+    }
+}
+
+make_global!(BINDINGS, Bindings, Bindings::from_toml(BINDINGS_PATH));
+
+pub use self::instance::*;
+
+/// Rereads the bindings file, keeping the old bindings if it isn't valid TOML.
+pub fn reload() {
+    match util::toml::try_toml_value_from_file(BINDINGS_PATH) {
+        Ok(value) => with_mut(|b| *b = Bindings::from_value(&value, BINDINGS_PATH)),
+        Err(e) => log!("Couldn't reload bindings: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    fn press(keys: &[KeyCode]) -> HashMap<KeyCode, bool> {
+        keys.iter().map(|k| (*k, true)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let trigger = Trigger::parse("ctrl+shift+F5").unwrap();
+        assert_eq!(trigger.to_string(), "ctrl+shift+F5");
+        assert_eq!(Trigger::parse("MouseLeft"), Some(Trigger::Mouse(MouseButton::Left)));
+        assert_eq!(Trigger::parse("w"), Some(Trigger::Key(Key::from(KeyCode::W))));
+        assert_eq!(Trigger::parse("hyper+W"), None);
+        assert_eq!(Trigger::parse("Nothing"), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut bindings = Bindings::new();
+        for name in ["1", "D0", "ctrl+F5", "shift+<", "Space", "MouseRight", "PageDown"].iter() {
+            bindings.bind(InputAction::Jump, Trigger::parse(name).unwrap());
+        }
+        bindings.bind(InputAction::Shoot, Trigger::parse("alt+9").unwrap());
+
+        let path = env::temp_dir().join("potato-dan-test-bindings.toml");
+        let path = path.to_str().unwrap();
+        bindings.save(path).unwrap();
+        assert_eq!(Bindings::from_toml(path).bindings, bindings.bindings);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut bindings = Bindings::new();
+        bindings.bind(InputAction::Shoot, Trigger::parse("F").unwrap());
+        bindings.bind(InputAction::Bom, Trigger::parse("F").unwrap());
+        bindings.bind(InputAction::Jump, Trigger::parse("Space").unwrap());
+
+        let conflicts = bindings.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].1, vec![InputAction::Shoot, InputAction::Bom]);
+    }
+
+    #[test]
+    fn test_chord_shadows_plain_key() {
+        let mut bindings = Bindings::new();
+        bindings.bind(InputAction::MoveDown, Trigger::parse("S").unwrap());
+        bindings.bind(InputAction::QuickSave, Trigger::parse("ctrl+S").unwrap());
+        let mouse = MouseState::default();
+
+        let active = bindings.active_actions(&press(&[KeyCode::S]), &mouse);
+        assert!(active.contains(&InputAction::MoveDown));
+        assert!(!active.contains(&InputAction::QuickSave));
+
+        let active = bindings.active_actions(&press(&[KeyCode::S, KeyCode::Ctrl]), &mouse);
+        assert!(!active.contains(&InputAction::MoveDown));
+        assert!(active.contains(&InputAction::QuickSave));
+    }
+}
@@ -67,6 +67,22 @@ impl From<VirtualKeyCode> for KeyCode {
             VirtualKeyCode::Numpad9  => KeyCode::NumPad9,
             VirtualKeyCode::Comma    => KeyCode::Comma,
             VirtualKeyCode::Period   => KeyCode::Period,
+            VirtualKeyCode::Key0     => KeyCode::D0,
+            VirtualKeyCode::Key1     => KeyCode::D1,
+            VirtualKeyCode::Key2     => KeyCode::D2,
+            VirtualKeyCode::Key3     => KeyCode::D3,
+            VirtualKeyCode::Key4     => KeyCode::D4,
+            VirtualKeyCode::Key5     => KeyCode::D5,
+            VirtualKeyCode::Key6     => KeyCode::D6,
+            VirtualKeyCode::Key7     => KeyCode::D7,
+            VirtualKeyCode::Key8     => KeyCode::D8,
+            VirtualKeyCode::Key9     => KeyCode::D9,
+            VirtualKeyCode::LShift   => KeyCode::Shift,
+            VirtualKeyCode::RShift   => KeyCode::Shift,
+            VirtualKeyCode::LControl => KeyCode::Ctrl,
+            VirtualKeyCode::RControl => KeyCode::Ctrl,
+            VirtualKeyCode::LAlt     => KeyCode::Alt,
+            VirtualKeyCode::RAlt     => KeyCode::Alt,
 
             _ => KeyCode::Unknown(' '),
         }
@@ -1,4 +1,4 @@
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Key {
     pub code: KeyCode,
     pub alt: bool,
@@ -30,7 +30,7 @@ impl From<char> for Key {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeyCode {
     D1, D2, D3, D4, D5, D6, D7, D8, D9, D0,
     A, B, C, D, E, F, G, H, I, J, K, L, M,
@@ -63,10 +63,83 @@ pub enum KeyCode {
     Period,
 
     Shift,
+    Ctrl,
+    Alt,
 
     Unknown(char),
 }
 
+impl KeyCode {
+    /// Parses a key name as written in data files, like "W", "F5" or "Space". The names the keys
+    /// print with are accepted too, so saved bindings load again.
+    pub fn from_name(name: &str) -> Option<KeyCode> {
+        let mut chars = name.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return match KeyCode::from(ch) {
+                KeyCode::Unknown(_) => None,
+                code => Some(code),
+            };
+        }
+
+        let code = match name.to_lowercase().as_str() {
+            "d0"       => KeyCode::D0,
+            "d1"       => KeyCode::D1,
+            "d2"       => KeyCode::D2,
+            "d3"       => KeyCode::D3,
+            "d4"       => KeyCode::D4,
+            "d5"       => KeyCode::D5,
+            "d6"       => KeyCode::D6,
+            "d7"       => KeyCode::D7,
+            "d8"       => KeyCode::D8,
+            "d9"       => KeyCode::D9,
+            "numpad0"  => KeyCode::NumPad0,
+            "numpad1"  => KeyCode::NumPad1,
+            "numpad2"  => KeyCode::NumPad2,
+            "numpad3"  => KeyCode::NumPad3,
+            "numpad4"  => KeyCode::NumPad4,
+            "numpad5"  => KeyCode::NumPad5,
+            "numpad6"  => KeyCode::NumPad6,
+            "numpad7"  => KeyCode::NumPad7,
+            "numpad8"  => KeyCode::NumPad8,
+            "numpad9"  => KeyCode::NumPad9,
+            "f1"       => KeyCode::F1,
+            "f2"       => KeyCode::F2,
+            "f3"       => KeyCode::F3,
+            "f4"       => KeyCode::F4,
+            "f5"       => KeyCode::F5,
+            "f6"       => KeyCode::F6,
+            "f7"       => KeyCode::F7,
+            "f8"       => KeyCode::F8,
+            "f9"       => KeyCode::F9,
+            "f10"      => KeyCode::F10,
+            "f11"      => KeyCode::F11,
+            "f12"      => KeyCode::F12,
+            "left"     => KeyCode::Left,
+            "right"    => KeyCode::Right,
+            "up"       => KeyCode::Up,
+            "down"     => KeyCode::Down,
+            "enter"    => KeyCode::Enter,
+            "space"    => KeyCode::Space,
+            "escape"   => KeyCode::Escape,
+            "delete"   => KeyCode::Delete,
+            "insert"   => KeyCode::Insert,
+            "home"     => KeyCode::Home,
+            "end"      => KeyCode::End,
+            "pageup"   => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "greaterthan" => KeyCode::GreaterThan,
+            "lessthan" => KeyCode::LessThan,
+            "comma"    => KeyCode::Comma,
+            "period"   => KeyCode::Period,
+            "shift"    => KeyCode::Shift,
+            "ctrl"     => KeyCode::Ctrl,
+            "alt"      => KeyCode::Alt,
+            _          => return None,
+        };
+        Some(code)
+    }
+}
+
 impl From<char> for KeyCode {
     fn from(ch: char) -> KeyCode {
         if ch.is_numeric() {
@@ -1,9 +1,42 @@
+pub mod bindings;
 pub mod keys;
+pub mod timestep;
 mod glium;
 
-#[derive(Copy, Clone, PartialEq, Debug, Default)]
+use std::collections::HashMap;
+
+use self::keys::KeyCode;
+use renderer::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct MouseState {
     pub pos: (i32, i32),
     pub pressed: (bool, bool, bool),
     pub wheel: f32,
 }
+
+/// The input for a single call to `state::game_step`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameInput {
+    pub keys: HashMap<KeyCode, bool>,
+    pub mouse: MouseState,
+    pub delta: f32,
+}
+
+impl FrameInput {
+    pub fn new(delta: f32) -> Self {
+        FrameInput {
+            keys: HashMap::new(),
+            mouse: MouseState {
+                pos: ((SCREEN_WIDTH / 2) as i32, (SCREEN_HEIGHT / 2) as i32),
+                ..Default::default()
+            },
+            delta: delta,
+        }
+    }
+
+    pub fn press(mut self, key: KeyCode) -> Self {
+        self.keys.insert(key, true);
+        self
+    }
+}
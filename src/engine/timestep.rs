@@ -0,0 +1,67 @@
+use debug;
+
+/// Never run more than this many ticks in one rendered frame, so a long stall doesn't make the
+/// simulation fall further and further behind.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// Seconds of game time simulated by one tick.
+pub fn tick_delta() -> f32 {
+    1.0 / debug::get("tick_rate").max(1.0)
+}
+
+/// Turns variable frame times into a whole number of fixed-length simulation ticks.
+pub struct FixedTimestep {
+    accumulator: f32,
+    tick_delta: f32,
+}
+
+impl FixedTimestep {
+    pub fn new() -> Self {
+        FixedTimestep {
+            accumulator: 0.0,
+            tick_delta: tick_delta(),
+        }
+    }
+
+    /// Adds the time taken by the last frame and returns how many ticks should be run.
+    pub fn advance(&mut self, frame_delta: f32) -> u32 {
+        self.tick_delta = tick_delta();
+        self.accumulator += frame_delta;
+
+        let ticks = (self.accumulator / self.tick_delta).floor() as u32;
+        if ticks > MAX_TICKS_PER_FRAME {
+            // Drop the time that didn't fit instead of catching up on it over the next frames.
+            self.accumulator = self.accumulator % self.tick_delta;
+            return MAX_TICKS_PER_FRAME;
+        }
+
+        self.accumulator -= ticks as f32 * self.tick_delta;
+        ticks
+    }
+
+    pub fn tick_delta(&self) -> f32 {
+        self.tick_delta
+    }
+
+    /// How far the renderer is between the last tick and the next one, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_delta).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let mut timestep = FixedTimestep::new();
+        let dt = timestep.tick_delta();
+        assert_eq!(timestep.advance(dt * 2.5), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 0.01);
+        assert_eq!(timestep.advance(dt * 0.6), 1);
+        assert_eq!(timestep.advance(dt * 100.0), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.advance(0.0), 0);
+        assert!(timestep.alpha() < 1.0);
+    }
+}
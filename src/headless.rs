@@ -0,0 +1,101 @@
+use std::time::Instant;
+
+use GameContext;
+use engine::FrameInput;
+use engine::timestep;
+use replay::Replay;
+use state;
+use util;
+
+pub const DEFAULT_FRAMES: u64 = 3600;
+
+/// Something that can feed input to the simulation without a window.
+pub trait InputSource {
+    /// Returns the input for the given frame, or `None` to stop early.
+    fn next_frame(&mut self, frame: u64, context: &GameContext) -> Option<FrameInput>;
+}
+
+/// Presses nothing, forever.
+pub struct Idle;
+
+impl InputSource for Idle {
+    fn next_frame(&mut self, _frame: u64, _context: &GameContext) -> Option<FrameInput> {
+        Some(FrameInput::new(timestep::tick_delta()))
+    }
+}
+
+/// Plays back a fixed list of inputs, then stops.
+pub struct Scripted {
+    frames: Vec<FrameInput>,
+}
+
+impl Scripted {
+    pub fn new(frames: Vec<FrameInput>) -> Self {
+        Scripted { frames: frames }
+    }
+}
+
+impl InputSource for Scripted {
+    fn next_frame(&mut self, frame: u64, _context: &GameContext) -> Option<FrameInput> {
+        self.frames.get(frame as usize).cloned()
+    }
+}
+
+pub fn step(context: &mut GameContext, input: &FrameInput) {
+    state::game_step(context, &input.keys, &input.mouse, input.delta);
+}
+
+/// Steps the world for up to `frames` frames. Returns the number of frames that were run.
+pub fn run<I: InputSource>(context: &mut GameContext, source: &mut I, frames: u64) -> u64 {
+    let mut ran = 0;
+    while ran < frames {
+        let input = match source.next_frame(ran, context) {
+            Some(i) => i,
+            None => break,
+        };
+        step(context, &input);
+        ran += 1;
+    }
+    ran
+}
+
+pub fn run_headless(seed: u64, frames: u64) {
+    let mut context = GameContext::new_headless(seed);
+    let start = Instant::now();
+
+    let ran = run(&mut context, &mut Idle, frames);
+
+    report(&context, ran, start);
+}
+
+pub fn run_replay(replay: Replay) {
+    let mut context = GameContext::new_headless(replay.seed);
+    let start = Instant::now();
+
+    let frames = replay.frames.len() as u64;
+    let ran = run(&mut context, &mut replay.into_player(), frames);
+
+    report(&context, ran, start);
+}
+
+fn report(context: &GameContext, ran: u64, start: Instant) {
+    let millis = util::get_duration_millis(&Instant::now().duration_since(start));
+    println!("Ran {} frames in {} ms | {} entities alive",
+             ran,
+             millis,
+             context.state.world.entities().len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::keys::KeyCode;
+
+    #[test]
+    fn test_run_scripted() {
+        let mut context = GameContext::new_headless(0);
+        let inputs = vec![FrameInput::new(timestep::tick_delta()).press(KeyCode::D); 10];
+        let ran = run(&mut context, &mut Scripted::new(inputs), 20);
+        assert_eq!(ran, 10);
+    }
+}
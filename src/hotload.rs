@@ -0,0 +1,203 @@
+//! Reloads data files when they change on disk, so things can be tuned without restarting.
+//!
+//! Files are polled for their modification times rather than watched, which is cheap enough at
+//! a couple of times a second and works the same everywhere.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use GameContext;
+use ai;
+use debug;
+use ecs::ballistics;
+use ecs::prefab;
+use engine::bindings;
+use renderer;
+use world::faction;
+
+pub const DATA_PATH: &str = "data";
+pub const POLL_INTERVAL_MS: u64 = 500;
+
+/// Remembers the modification time of every file under a directory.
+pub struct FileWatcher {
+    root: PathBuf,
+    mtimes: HashMap<PathBuf, SystemTime>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(root: &str, interval: Duration) -> Self {
+        let mut watcher = FileWatcher {
+            root: PathBuf::from(root),
+            mtimes: HashMap::new(),
+            interval: interval,
+            last_poll: Instant::now(),
+        };
+        watcher.changed();
+        watcher
+    }
+
+    /// Like `changed`, but only looks at the disk once the poll interval has passed.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        self.changed()
+    }
+
+    /// The files that were created or modified since the last call, sorted by path.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut mtimes = HashMap::new();
+        scan(&self.root, &mut mtimes);
+
+        let mut changed: Vec<PathBuf> = mtimes.iter()
+            .filter(|&(path, time)| self.mtimes.get(path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+
+        self.mtimes = mtimes;
+        changed
+    }
+}
+
+fn scan(dir: &Path, mtimes: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+
+        // Hidden files are editor backups and the like, and `data/.packed` is written to by
+        // reloading the atlas, which would otherwise set off another reload.
+        let hidden = path.file_name().and_then(|n| n.to_str()).map_or(true, |n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if metadata.is_dir() {
+            scan(&path, mtimes);
+        } else if let Ok(time) = metadata.modified() {
+            mtimes.insert(path, time);
+        }
+    }
+}
+
+/// What has to be reloaded after a file changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    DebugVars,
+    Actions,
+    Bullets,
+    Factions,
+    Prefabs,
+    Bindings,
+    Sprites,
+    Shaders,
+}
+
+impl DataKind {
+    pub fn of(path: &Path) -> Option<DataKind> {
+        if path == Path::new(debug::gui::VARS_PATH) {
+            Some(DataKind::DebugVars)
+        } else if path == Path::new(ai::ACTIONS_PATH) {
+            Some(DataKind::Actions)
+        } else if path == Path::new(ballistics::BULLETS_PATH) {
+            Some(DataKind::Bullets)
+        } else if path == Path::new(faction::FACTIONS_PATH) {
+            Some(DataKind::Factions)
+        } else if path == Path::new(bindings::BINDINGS_PATH) {
+            Some(DataKind::Bindings)
+        } else if path.starts_with(prefab::PREFABS_PATH) {
+            Some(DataKind::Prefabs)
+        } else if path == Path::new(renderer::SPRITES_PATH) || path.starts_with("data/texture") {
+            Some(DataKind::Sprites)
+        } else if path.starts_with("data/shaders") {
+            Some(DataKind::Shaders)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reloads whatever the changed files feed into. Errors are logged and leave the old data in
+/// place.
+pub fn reload(context: &mut GameContext, changed: &[PathBuf]) {
+    let mut done = HashSet::new();
+
+    for path in changed.iter() {
+        let kind = match DataKind::of(path) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        if !done.insert(kind) {
+            continue;
+        }
+
+        log!("{} changed, reloading {:?}", path.display(), kind);
+
+        match kind {
+            DataKind::DebugVars => debug::reload_vars(),
+            DataKind::Actions => {
+                ai::reload_planner();
+                ai::replan_all(&context.state.world);
+            },
+            DataKind::Bullets => ballistics::reload(),
+            DataKind::Factions => faction::reload(),
+            DataKind::Prefabs => prefab::reload(),
+            DataKind::Bindings => bindings::reload(),
+            DataKind::Sprites => if !context.headless {
+                renderer::with_mut(|rc| rc.reload_atlas())
+            },
+            DataKind::Shaders => if !context.headless {
+                renderer::with_mut(|rc| rc.reload_shaders())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_kinds() {
+        assert_eq!(DataKind::of(Path::new("data/actions.toml")), Some(DataKind::Actions));
+        assert_eq!(DataKind::of(Path::new("data/factions.toml")), Some(DataKind::Factions));
+        assert_eq!(DataKind::of(Path::new("data/prefabs/weapons.toml")), Some(DataKind::Prefabs));
+        assert_eq!(DataKind::of(Path::new("data/texture/chara.png")), Some(DataKind::Sprites));
+        assert_eq!(DataKind::of(Path::new("data/pc-9800.ttf")), None);
+    }
+
+    #[test]
+    fn test_watcher() {
+        let dir = env::temp_dir().join("potato-dan-test-watch");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        File::create(dir.join("a.toml")).unwrap();
+
+        let mut watcher = FileWatcher::new(dir.to_str().unwrap(), Duration::from_millis(0));
+        assert!(watcher.changed().is_empty());
+
+        File::create(dir.join("sub/b.toml")).unwrap().write_all(b"b = 1").unwrap();
+        File::create(dir.join(".hidden")).unwrap();
+        assert_eq!(watcher.changed(), vec![dir.join("sub/b.toml")]);
+        assert!(watcher.changed().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,165 @@
+//! Sensors and actions for the GOAP planner written in Lua.
+//!
+//! Scripts declare them with `ai.sensor(name, f)` and `ai.action(name, f)`, and `actions.toml`
+//! refers to them by the same names. Both functions are called with the handle of the thinking
+//! entity and the handle of its target, or nil. A sensor returns a boolean; an action returns
+//! `ai.go(dir)`, `ai.shoot(angle)`, `ai.melee(angle)`, `ai.reload()` or `ai.wait()`. Sensors that
+//! no action in `actions.toml` mentions are never run, and the rest are run for every AI at once.
+//!
+//! The AI runs with a read-only world, so these see the snapshot from the last time scripts ran,
+//! and anything they ask the world to do is applied the next time scripts run.
+
+use calx_ecs::Entity;
+use hlua::{self, Lua};
+
+use ai::Action;
+use point::DIRECTIONS;
+use lua::world::handle_for;
+
+pub struct ScriptAi {
+    sensors: Vec<String>,
+    actions: Vec<String>,
+}
+
+impl ScriptAi {
+    fn new() -> Self {
+        ScriptAi {
+            sensors: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+}
+
+make_global!(SCRIPT_AI, ScriptAi, ScriptAi::new());
+
+pub fn reset() {
+    instance::with_mut(|s| *s = ScriptAi::new());
+}
+
+pub fn has_sensor(name: &str) -> bool {
+    instance::with(|s| s.sensors.iter().any(|n| n == name))
+}
+
+pub fn has_action(name: &str) -> bool {
+    instance::with(|s| s.actions.iter().any(|n| n == name))
+}
+
+fn target_arg(target: Option<Entity>) -> String {
+    match target {
+        Some(t) => handle_for(t).to_string(),
+        None => "nil".to_string(),
+    }
+}
+
+fn call_args(name: &str, entity: Entity, target: Option<Entity>) -> String {
+    format!("{:?}, {}, {}", name, handle_for(entity), target_arg(target))
+}
+
+/// Runs every sensor in `names` for every entity and target in `thinkers`, in a single call to
+/// Lua. Returns the results for each of `thinkers` in turn, in the order of `names`.
+pub fn sense_all(names: &[String], thinkers: &[(Entity, Option<Entity>)]) -> Vec<Vec<bool>> {
+    if names.is_empty() || thinkers.is_empty() {
+        return thinkers.iter().map(|_| Vec::new()).collect();
+    }
+
+    let names_arg: Vec<String> = names.iter().map(|n| format!("{:?}", n)).collect();
+    let calls_arg: Vec<String> = thinkers.iter()
+        .map(|&(entity, target)| format!("{{ {}, {} }}", handle_for(entity), target_arg(target)))
+        .collect();
+    let code = format!("return ai_sense_all({{ {} }}, {{ {} }})",
+                       names_arg.join(", "),
+                       calls_arg.join(", "));
+
+    match super::with_mut(|lua| lua.execute::<String>(&code)) {
+        Ok(results) => parse_sensed(&results, names.len(), thinkers.len()),
+        Err(e) => {
+            log!("Error in Lua sensors: {:?}", e);
+            thinkers.iter().map(|_| vec![false; names.len()]).collect()
+        },
+    }
+}
+
+/// Splits the string of "1"s and "0"s `ai_sense_all` returns into a row of results per thinker.
+/// Anything missing counts as false.
+fn parse_sensed(results: &str, sensors: usize, thinkers: usize) -> Vec<Vec<bool>> {
+    let mut results = results.chars().map(|c| c == '1');
+    (0..thinkers)
+        .map(|_| (0..sensors).map(|_| results.next().unwrap_or(false)).collect())
+        .collect()
+}
+
+pub fn act(name: &str, entity: Entity, target: Option<Entity>) -> Action {
+    let code = format!("return ai_act({})", call_args(name, entity, target));
+    let result = super::with_mut(|lua| lua.execute::<String>(&code));
+    let action = result.map_err(|e| format!("{:?}", e)).and_then(|s| parse_action(&s));
+    match action {
+        Ok(action) => action,
+        Err(e) => {
+            log!("Error in Lua action {}: {}", name, e);
+            Action::Wait
+        },
+    }
+}
+
+fn parse_action(s: &str) -> Result<Action, String> {
+    let mut words = s.split_whitespace();
+    let action = match (words.next(), words.next()) {
+        (Some("wait"), None) => Some(Action::Wait),
+        (Some("reload"), None) => Some(Action::Reload),
+        (Some("go"), Some(dir)) => {
+            DIRECTIONS.iter().find(|d| format!("{:?}", d) == dir).map(|d| Action::Go(*d))
+        },
+        (Some("shoot"), Some(angle)) => angle.parse::<f32>().ok().map(Action::Shoot),
+        (Some("melee"), Some(angle)) => angle.parse::<f32>().ok().map(Action::Melee),
+        _ => None,
+    };
+    action.ok_or(format!("Invalid action \"{}\"", s))
+}
+
+fn lua_register_sensor(name: String) {
+    instance::with_mut(|s| if !s.sensors.contains(&name) {
+        s.sensors.push(name)
+    });
+}
+
+fn lua_register_action(name: String) {
+    instance::with_mut(|s| if !s.actions.contains(&name) {
+        s.actions.push(name)
+    });
+}
+
+pub fn add_lua_interop(lua: &mut Lua) {
+    lua.set("ai_register_sensor", hlua::function1(lua_register_sensor));
+    lua.set("ai_register_action", hlua::function1(lua_register_action));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use point::Direction;
+
+    #[test]
+    fn test_parse_action() {
+        assert!(match parse_action("go NE") {
+            Ok(Action::Go(Direction::NE)) => true,
+            _ => false,
+        });
+        assert!(match parse_action("shoot 1.5") {
+            Ok(Action::Shoot(a)) => a == 1.5,
+            _ => false,
+        });
+        assert!(match parse_action("reload") {
+            Ok(Action::Reload) => true,
+            _ => false,
+        });
+        assert!(parse_action("go nowhere").is_err());
+        assert!(parse_action("dance").is_err());
+    }
+
+    #[test]
+    fn test_parse_sensed() {
+        assert_eq!(parse_sensed("101100", 3, 2),
+                   vec![vec![true, false, true], vec![true, false, false]]);
+        assert_eq!(parse_sensed("1", 2, 1), vec![vec![true, false]]);
+    }
+}
@@ -1,28 +1,29 @@
 use hlua::{self, Lua};
-use slog::Logger;
 
-use log;
+use debug;
 
-make_global!(LUA_LOG, Logger, log::make_logger("lua"));
+fn lua_log(level: &str, text: &str) {
+    debug::log(&format!("[lua {}] {}", level, text));
+}
 
 pub fn lua_log_info(text: String) {
-    instance::with(|l| info!(l, text));
+    lua_log("info", &text);
 }
 
 pub fn lua_log_warn(text: String) {
-    instance::with(|l| warn!(l, text));
+    lua_log("warn", &text);
 }
 
 pub fn lua_log_error(text: String) {
-    instance::with(|l| error!(l, text));
+    lua_log("error", &text);
 }
 
 pub fn lua_log_debug(text: String) {
-    instance::with(|l| debug!(l, text));
+    lua_log("debug", &text);
 }
 
 pub fn lua_log_trace(text: String) {
-    instance::with(|l| trace!(l, text));
+    lua_log("trace", &text);
 }
 
 pub fn add_lua_interop(lua: &mut Lua) {
@@ -1,13 +1,17 @@
+pub mod ai;
 pub mod log;
+mod prefab;
 mod random;
+pub mod world;
 pub use self::log::*;
 
 use std::fs::File;
 use std::io::Read;
 
+use glob;
 use hlua::{self, Lua};
 
-use prefab;
+use world::World;
 
 make_global!(LUA_CONTEXT, Lua<'static>, init());
 
@@ -32,17 +36,57 @@ fn open_libs(lua: &mut Lua) -> Result<(), hlua::LuaError> {
 
     self::log::add_lua_interop(lua);
     self::random::add_lua_interop(lua);
-    prefab::add_lua_interop(lua);
+    self::prefab::add_lua_interop(lua);
+    self::world::add_lua_interop(lua);
+    self::ai::add_lua_interop(lua);
 
     run_script(lua, "lib/init")
 }
 
+/// Runs every script at the top of the script directory, in alphabetical order. A broken script
+/// is logged and skipped.
+fn load_game_scripts(lua: &mut Lua) {
+    for entry in glob::glob(&format!("{}/*.lua", SCRIPT_DIRECTORY)).unwrap() {
+        if let Ok(path) = entry {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            if let Err(e) = run_script(lua, &name) {
+                log!("Error in script {}: {:?}", name, e);
+            }
+        }
+    }
+}
+
 fn init() -> Lua<'static> {
     let mut lua = Lua::new();
     open_libs(&mut lua).unwrap();
+    load_game_scripts(&mut lua);
     lua
 }
 
+/// Throws away all script state and loads the scripts again.
+pub fn reset() {
+    self::world::reset();
+    self::ai::reset();
+    with_mut(|lua| *lua = init());
+}
+
+/// Calls the functions scripts registered for `hook` with `on`. `args` is pasted into the call
+/// as Lua source. If nothing is registered for it, the world isn't even copied over to Lua.
+pub fn run_hooks(world: &mut World, hook: &str, args: &str) {
+    // The scripts register their hooks as they are loaded, which is the first time Lua is used.
+    with(|_| ());
+    if !self::world::has_hook(hook) {
+        return;
+    }
+
+    let code = if args.is_empty() {
+        format!("run_hooks(\"{}\")", hook)
+    } else {
+        format!("run_hooks(\"{}\", {})", hook, args)
+    };
+    self::world::run(world, &code);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,18 @@
+use hlua::{self, Lua};
+
+use ecs::prefab;
+
+fn lua_names() -> Vec<String> {
+    prefab::with(|p| p.names())
+}
+
+fn lua_exists(name: String) -> bool {
+    prefab::with(|p| p.resolve(&name).is_ok())
+}
+
+pub fn add_lua_interop(lua: &mut Lua) {
+    let mut prefab_namespace = lua.empty_array("prefab");
+
+    prefab_namespace.set("names", hlua::function0(lua_names));
+    prefab_namespace.set("exists", hlua::function1(lua_exists));
+}
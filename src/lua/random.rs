@@ -1,30 +1,29 @@
-use prefab::{PrefabError, PrefabResult};
-use rand::{self, Rng};
+use rand::Rng;
 use hlua::{self, Lua};
 
-fn lua_between(a: i32, b: i32) -> PrefabResult<i32> {
+use lua::world::with_rng;
+
+fn lua_between(a: i32, b: i32) -> i32 {
     if a == b {
-        return Ok(a);
-    }
-    if a > b {
-        return Err(PrefabError::BadRange(a, b))
+        return a;
     }
-    Ok(rand::thread_rng().gen_range(a, b))
+    let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+    with_rng(|rng| rng.gen_range(lo, hi))
 }
 
-fn lua_zero_to(n: i32) -> PrefabResult<i32> {
+fn lua_zero_to(n: i32) -> i32 {
     if n <= 0 {
-        return Err(PrefabError::BadRange(0, n))
+        return 0;
     }
-    Ok(rand::thread_rng().gen_range(0, n))
+    with_rng(|rng| rng.gen_range(0, n))
 }
 
 fn lua_chance(n: f32) -> bool {
-    rand::thread_rng().next_f32() < n
+    with_rng(|rng| rng.next_f32() < n)
 }
 
 fn lua_coinflip() -> bool {
-    rand::thread_rng().gen()
+    with_rng(|rng| rng.gen())
 }
 
 pub fn add_lua_interop(lua: &mut Lua) {
@@ -0,0 +1,356 @@
+//! The `world` namespace for scripts.
+//!
+//! Scripts never touch the `World` directly. Before a script runs, the parts of the world it can
+//! look at are copied into a snapshot, and everything it asks to change is queued and applied in
+//! order once it returns. Entities are passed to Lua as integer handles that stay the same for as
+//! long as the Lua context lives, so scripts can keep them between calls.
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use calx_ecs::Entity;
+use hlua::{self, Lua};
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use ecs::components::{DamageKind, HitZone};
+use ecs::query;
+use point::Point;
+use world::{Event, World};
+use world::rng::RngStream;
+
+pub type Handle = u32;
+
+enum ScriptCommand {
+    Spawn(Handle, String, Point),
+    Equip(Handle, Handle),
+    PushEvent(Handle, Event),
+}
+
+#[derive(Clone)]
+struct Snapshot {
+    pos: Point,
+    name: Option<String>,
+    health: Option<(i32, i32)>,
+}
+
+struct ScriptWorld {
+    /// Handles of entities queued to spawn are `None` until the spawn is applied. Handles of
+    /// removed entities are dropped, and never given out again.
+    handles: HashMap<Handle, Option<Entity>>,
+    by_entity: HashMap<Entity, Handle>,
+    next_handle: Handle,
+    /// Hooks scripts have registered functions for, so the others can be skipped.
+    hooks: HashSet<String>,
+
+    entities: Vec<Handle>,
+    snapshots: HashMap<Handle, Snapshot>,
+    player: Option<Handle>,
+    size: (u32, u32),
+
+    commands: Vec<ScriptCommand>,
+    rng: XorShiftRng,
+}
+
+impl ScriptWorld {
+    fn new() -> Self {
+        ScriptWorld {
+            handles: HashMap::new(),
+            by_entity: HashMap::new(),
+            next_handle: 0,
+            hooks: HashSet::new(),
+            entities: Vec::new(),
+            snapshots: HashMap::new(),
+            player: None,
+            size: (0, 0),
+            commands: Vec::new(),
+            rng: XorShiftRng::new_unseeded(),
+        }
+    }
+
+    fn handle(&mut self, entity: Entity) -> Handle {
+        if let Some(handle) = self.by_entity.get(&entity) {
+            return *handle;
+        }
+        let handle = self.new_handle();
+        self.set_entity(handle, entity);
+        handle
+    }
+
+    fn new_handle(&mut self) -> Handle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.handles.insert(handle, None);
+        handle
+    }
+
+    fn set_entity(&mut self, handle: Handle, entity: Entity) {
+        self.handles.insert(handle, Some(entity));
+        self.by_entity.insert(entity, handle);
+    }
+
+    fn entity(&self, handle: Handle) -> Option<Entity> {
+        self.handles.get(&handle).and_then(|e| *e)
+    }
+
+    fn release(&mut self, entity: Entity) {
+        if let Some(handle) = self.by_entity.remove(&entity) {
+            self.handles.remove(&handle);
+            self.snapshots.remove(&handle);
+        }
+    }
+
+    fn sync(&mut self, world: &mut World) {
+        self.snapshot(world);
+
+        let seed = world.rng(RngStream::Script).gen::<[u32; 4]>();
+        self.rng = XorShiftRng::from_seed(seed);
+    }
+
+    fn snapshot(&mut self, world: &World) {
+        self.entities.clear();
+        self.snapshots.clear();
+
+        for (entity, pos) in query::iter(&world.ecs().positions) {
+            let snapshot = Snapshot {
+                pos: pos.pos,
+                name: world.ecs().names.get(entity).map(|n| n.name.clone()),
+                health: world.ecs().healths.get(entity).map(|h| (h.hit_points, h.max_hit_points)),
+            };
+            let handle = self.handle(entity);
+            self.entities.push(handle);
+            self.snapshots.insert(handle, snapshot);
+        }
+
+        let player = world.player().map(|p| self.handle(p));
+        self.player = player;
+        self.size = world.size();
+    }
+}
+
+make_global!(SCRIPT_WORLD, ScriptWorld, ScriptWorld::new());
+
+/// Runs `f` with the random number generator scripts draw from. It is reseeded from the world
+/// every time a script runs, and before the AI thinks, so replays stay deterministic.
+pub fn with_rng<F, T>(f: F) -> T
+    where F: FnOnce(&mut XorShiftRng) -> T {
+    instance::with_mut(|s| f(&mut s.rng))
+}
+
+/// Copies the world over to scripts again without running any, for script code the AI calls,
+/// which would otherwise see the world as it was when a hook last ran.
+pub fn refresh(world: &World) {
+    profile_scope!("lua_refresh");
+    instance::with_mut(|s| s.snapshot(world));
+}
+
+/// Reseeds the generator scripts draw from, for script code the AI calls outside of `run`.
+pub fn seed_rng(seed: [u32; 4]) {
+    instance::with_mut(|s| s.rng = XorShiftRng::from_seed(seed));
+}
+
+/// The handle scripts know `entity` by.
+pub fn handle_for(entity: Entity) -> Handle {
+    instance::with_mut(|s| s.handle(entity))
+}
+
+/// The entity scripts know by `handle`, if it has been spawned.
+pub fn entity_for(handle: Handle) -> Option<Entity> {
+    instance::with(|s| s.entity(handle))
+}
+
+/// Forgets the handle of an entity that is being removed from the world.
+pub fn release(entity: Entity) {
+    instance::with_mut(|s| s.release(entity));
+}
+
+/// Whether any script has registered a function for `hook`.
+pub fn has_hook(hook: &str) -> bool {
+    instance::with(|s| s.hooks.contains(hook))
+}
+
+/// Forgets every handle and hook, for when the Lua context is thrown away.
+pub fn reset() {
+    instance::with_mut(|s| *s = ScriptWorld::new());
+}
+
+/// Runs a chunk of Lua against the world and applies what it asked for.
+pub fn run(world: &mut World, code: &str) {
+    profile_scope!("lua");
+    instance::with_mut(|s| s.sync(world));
+
+    let result = super::with_mut(|lua| lua.execute::<()>(code));
+    if let Err(e) = result {
+        log!("Lua error: {:?}", e);
+    }
+
+    apply_commands(world);
+}
+
+fn apply_commands(world: &mut World) {
+    let commands = instance::with_mut(|s| mem::replace(&mut s.commands, Vec::new()));
+
+    for command in commands {
+        match command {
+            ScriptCommand::Spawn(handle, name, pos) => {
+                if let Some(entity) = world.spawn_prefab(&name, pos) {
+                    instance::with_mut(|s| s.set_entity(handle, entity));
+                }
+            },
+            ScriptCommand::Equip(holder, item) => {
+                let entities = instance::with(|s| (s.entity(holder), s.entity(item)));
+                if let (Some(holder_entity), Some(item_entity)) = entities {
+                    if world.contains(holder_entity) && world.contains(item_entity) &&
+                        !world.equip(holder_entity, item_entity) {
+                        log!("Script tried to equip {} with {}, which it can't hold", holder, item);
+                    }
+                }
+            },
+            ScriptCommand::PushEvent(handle, event) => {
+                if let Some(entity) = instance::with(|s| s.entity(handle)) {
+                    if world.contains(entity) {
+                        world.push_event(event, entity);
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn queue(command: ScriptCommand) {
+    instance::with_mut(|s| s.commands.push(command));
+}
+
+fn lua_spawn(name: String, x: f32, z: f32) -> Handle {
+    let handle = instance::with_mut(|s| s.new_handle());
+    queue(ScriptCommand::Spawn(handle, name, Point::new(x, 0.0, z)));
+    handle
+}
+
+fn lua_equip(holder: Handle, item: Handle) {
+    queue(ScriptCommand::Equip(holder, item));
+}
+
+fn script_damage(handle: Handle, amount: i32, kind: DamageKind) {
+    let event = Event::Damaged { amount: amount, source: None, kind: kind, zone: HitZone::Body };
+    queue(ScriptCommand::PushEvent(handle, event));
+}
+
+fn lua_damage(handle: Handle, amount: i32) {
+    script_damage(handle, amount, DamageKind::Ballistic);
+}
+
+fn lua_burn(handle: Handle, amount: i32) {
+    script_damage(handle, amount, DamageKind::Fire);
+}
+
+fn lua_kill(handle: Handle) {
+    queue(ScriptCommand::PushEvent(handle, Event::Killed { by: None }));
+}
+
+fn lua_destroy(handle: Handle) {
+    queue(ScriptCommand::PushEvent(handle, Event::Destroy));
+}
+
+fn lua_position(handle: Handle) -> Option<(f32, f32, f32)> {
+    instance::with(|s| s.snapshots.get(&handle).map(|snap| (snap.pos.x, snap.pos.y, snap.pos.z)))
+}
+
+fn lua_name(handle: Handle) -> Option<String> {
+    instance::with(|s| s.snapshots.get(&handle).and_then(|snap| snap.name.clone()))
+}
+
+fn lua_health(handle: Handle) -> Option<(i32, i32)> {
+    instance::with(|s| s.snapshots.get(&handle).and_then(|snap| snap.health))
+}
+
+fn lua_exists(handle: Handle) -> bool {
+    instance::with(|s| s.snapshots.contains_key(&handle))
+}
+
+fn lua_entities() -> Vec<Handle> {
+    instance::with(|s| s.entities.clone())
+}
+
+fn lua_player() -> Option<Handle> {
+    instance::with(|s| s.player)
+}
+
+fn lua_size() -> (u32, u32) {
+    instance::with(|s| s.size)
+}
+
+fn lua_register_hook(name: String) {
+    instance::with_mut(|s| {
+        s.hooks.insert(name);
+    });
+}
+
+pub fn add_lua_interop(lua: &mut Lua) {
+    lua.set("register_hook", hlua::function1(lua_register_hook));
+
+    let mut world_namespace = lua.empty_array("world");
+
+    world_namespace.set("spawn", hlua::function3(lua_spawn));
+    world_namespace.set("equip", hlua::function2(lua_equip));
+    world_namespace.set("damage", hlua::function2(lua_damage));
+    world_namespace.set("burn", hlua::function2(lua_burn));
+    world_namespace.set("kill", hlua::function1(lua_kill));
+    world_namespace.set("destroy", hlua::function1(lua_destroy));
+    world_namespace.set("position", hlua::function1(lua_position));
+    world_namespace.set("name", hlua::function1(lua_name));
+    world_namespace.set("health", hlua::function1(lua_health));
+    world_namespace.set("exists", hlua::function1(lua_exists));
+    world_namespace.set("entities", hlua::function0(lua_entities));
+    world_namespace.set("player", hlua::function0(lua_player));
+    world_namespace.set("size", hlua::function0(lua_size));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_and_equip() {
+        let mut world = World::new(32, 32, 1);
+        run(&mut world, "
+dood = world.spawn('dood', 5, 5)
+gun = world.spawn('smg', 5, 5)
+world.equip(dood, gun)
+");
+        assert_eq!(query::iter(&world.ecs().ais).count(), 1);
+
+        run(&mut world, "
+x, y, z = world.position(dood)
+assert(x == 5 and z == 5)
+assert(world.name(dood) == 'Dood')
+world.kill(dood)
+");
+        world.handle_events();
+        world.purge_dead();
+        assert_eq!(query::iter(&world.ecs().ais).count(), 0);
+    }
+
+    #[test]
+    fn test_handles_released() {
+        let mut world = World::new(32, 32, 1);
+        let dood = world.spawn_prefab("dood", Point::new(5.0, 0.0, 5.0)).unwrap();
+        let handle = handle_for(dood);
+        assert_eq!(entity_for(handle), Some(dood));
+
+        world.remove(dood);
+        assert_eq!(entity_for(handle), None);
+        assert!(instance::with(|s| !s.by_entity.contains_key(&dood)));
+    }
+
+    #[test]
+    fn test_refresh() {
+        let mut world = World::new(32, 32, 1);
+        run(&mut world, "");
+        let dood = world.spawn_prefab("dood", Point::new(5.0, 0.0, 5.0)).unwrap();
+        let handle = handle_for(dood);
+        assert!(instance::with(|s| !s.snapshots.contains_key(&handle)));
+
+        refresh(&world);
+        assert!(instance::with(|s| s.snapshots.contains_key(&handle)));
+    }
+}
@@ -27,3 +27,11 @@ macro_rules! log(
         ::debug::log(&format!($tag, $($args)+));
     };
 );
+
+/// Times the rest of the enclosing block in the profiler.
+#[macro_export]
+macro_rules! profile_scope(
+    ($name:expr) => {
+        let _profile_scope = ::debug::profile::scope($name);
+    };
+);
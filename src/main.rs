@@ -41,50 +41,120 @@ mod debug;
 mod ecs;
 mod engine;
 mod graphics;
+mod headless;
+mod hotload;
+mod lua;
 mod point;
 mod renderer;
+mod replay;
+mod scheduler;
 mod state;
 mod util;
 mod world;
 
 use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+use rand::Rng;
 use glium::glutin::{self, VirtualKeyCode, ElementState};
 use glium::glutin::WindowEvent::*;
 use glium::glutin::ElementState::Pressed;
 use glium::glutin::{Event, MouseButton, MouseScrollDelta, TouchPhase};
 use state::GameState;
 use engine::keys::KeyCode;
-use engine::MouseState;
+use engine::{FrameInput, MouseState};
+use engine::timestep::FixedTimestep;
+use debug::profile;
+use hotload::FileWatcher;
+use replay::{Replay, ReplayPlayer};
 
 pub struct GameContext {
     pub state: GameState,
+
+    /// If true, nothing in the game step may touch the renderer.
+    pub headless: bool,
 }
 
 impl GameContext {
-    pub fn new() -> Self {
+    pub fn new(seed: u64) -> Self {
+        GameContext {
+            state: GameState::new(seed),
+            headless: false,
+        }
+    }
+
+    pub fn new_headless(seed: u64) -> Self {
         GameContext {
-            state: GameState::new(),
+            state: GameState::new(seed),
+            headless: true,
         }
     }
 }
 
+fn arg_value<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|idx| args.get(idx + 1))
+        .and_then(|s| s.parse::<T>().ok())
+}
+
 fn main() {
-    game_loop();
+    let args: Vec<String> = env::args().collect();
+
+    let replay = arg_value::<String>(&args, "--replay").map(|path| {
+        Replay::load(&path).expect(&format!("Could not load replay {}", path))
+    });
+    let record_path = arg_value::<String>(&args, "--record");
+
+    let seed = replay.as_ref()
+                     .map(|r| r.seed)
+                     .or_else(|| arg_value(&args, "--seed"))
+                     .unwrap_or_else(|| rand::thread_rng().gen::<u64>());
+    println!("Seed: {}", seed);
+
+    if args.iter().any(|a| a == "--headless") {
+        match replay {
+            Some(replay) => headless::run_replay(replay),
+            None => {
+                let frames = arg_value(&args, "--headless").unwrap_or(headless::DEFAULT_FRAMES);
+                headless::run_headless(seed, frames);
+            },
+        }
+    } else {
+        game_loop(seed, replay.map(|r| r.into_player()), record_path);
+    }
 
     println!("Exited cleanly.");
 }
 
-fn game_loop() {
-    let mut context = GameContext::new();
+fn game_loop(seed: u64, mut playback: Option<ReplayPlayer>, record_path: Option<String>) {
+    let mut context = GameContext::new(seed);
     let mut keys = HashMap::new();
     let mut mouse_state = MouseState::default();
+    let mut recording = record_path.as_ref().map(|_| Replay::new(seed));
+    let mut timestep = FixedTimestep::new();
+
+    // Replays can't reproduce data that changed halfway through, so don't reload while one is
+    // being played or recorded, and don't let the console change the world either.
+    debug::set_replay_active(playback.is_some() || recording.is_some());
+    let mut watcher = if playback.is_none() && recording.is_none() {
+        Some(FileWatcher::new(hotload::DATA_PATH, Duration::from_millis(hotload::POLL_INTERVAL_MS)))
+    } else {
+        None
+    };
 
-    renderer::with_mut(|rc| rc.update(&context.state.world));
+    renderer::with_mut(|rc| rc.update(&context.state.world, 1.0));
 
     'outer: loop {
+        profile::begin_frame();
+
         let mut resize = None;
         let mut quit = false;
         let mut delta = 0.0;
+        let mut imgui_keys = Vec::new();
+        let mut imgui_chars = Vec::new();
+        let wants_keyboard = debug::wants_keyboard();
         renderer::with_mut(|rc| {
             delta = rc.delta();
             rc.poll_events(|event| match event {
@@ -99,9 +169,15 @@ fn game_loop() {
 
                     match event {
                         KeyboardInput { input, .. } => {
+                            if let Some(code) = input.virtual_keycode {
+                                imgui_keys.push((code, input.state == Pressed));
+                            }
+
+                            // Keys typed into the debug GUI don't also move the player.
                             if let ElementState::Pressed = input.state {
                                 if let Some(code) = input.virtual_keycode {
                                     match code {
+                                        _ if wants_keyboard => (),
                                         VirtualKeyCode::Escape => quit = true,
                                         _ => {
                                             let key = KeyCode::from(code);
@@ -121,6 +197,7 @@ fn game_loop() {
                                 }
                             }
                         },
+                        ReceivedCharacter(ch) => imgui_chars.push(ch),
                         CursorMoved { position: (x, y), .. } => mouse_state.pos = (x as i32, y as i32),
                         MouseInput { state, button, .. } => {
                             match button {
@@ -146,6 +223,13 @@ fn game_loop() {
                 _ => (),
             });
 
+            for (code, pressed) in imgui_keys.drain(..) {
+                rc.set_key(code, pressed);
+            }
+            for ch in imgui_chars.drain(..) {
+                rc.add_input_character(ch);
+            }
+
             false
         });
 
@@ -157,16 +241,47 @@ fn game_loop() {
             renderer::with_mut(|renderer| {
                 renderer.set_viewport(w, h);
                 renderer.set_mouse(&mut mouse_state);
-                renderer.update(&context.state.world);
-                renderer.render();
+                renderer.update(&context.state.world, timestep.alpha());
+                renderer.render(&mut context.state.world);
             });
         }
 
-        // Ensure that the renderer isn't borrowed during the game step, so it can be used in
-        // the middle of any game routine (like querying the player for input)
-        state::game_step(&mut context, &keys, &mouse_state, delta);
+        if let Some(ref mut watcher) = watcher {
+            let changed = watcher.poll();
+            if !changed.is_empty() {
+                hotload::reload(&mut context, &changed);
+            }
+        }
 
-        renderer::with_mut(|renderer| renderer.update(&context.state.world));
+        let ticks = timestep.advance(delta * debug::time_scale());
+        for _ in 0..ticks {
+            let mut input = FrameInput {
+                keys: keys.clone(),
+                mouse: mouse_state,
+                delta: timestep.tick_delta(),
+            };
+
+            if let Some(recorded) = playback.as_mut().and_then(|p| p.next()) {
+                input = recorded;
+            }
+
+            if playback.as_ref().map_or(false, |p| p.is_finished()) {
+                println!("Replay finished.");
+                playback = None;
+                debug::set_replay_active(recording.is_some());
+            }
+
+            if let Some(ref mut replay) = recording {
+                replay.record(&input);
+            }
+
+            // Ensure that the renderer isn't borrowed during the game step, so it can be used in
+            // the middle of any game routine (like querying the player for input)
+            state::game_step(&mut context, &input.keys, &input.mouse, input.delta);
+        }
+
+        let alpha = timestep.alpha();
+        renderer::with_mut(|renderer| renderer.update(&context.state.world, alpha));
 
         renderer::with_mut(|renderer| {
             if let Some((w, h)) = resize {
@@ -174,8 +289,17 @@ fn game_loop() {
             }
 
             renderer.set_mouse(&mut mouse_state);
-            renderer.render();
+            renderer.render(&mut context.state.world);
             renderer.step_frame();
         });
+
+        profile::end_frame();
+    }
+
+    if let (Some(replay), Some(path)) = (recording, record_path) {
+        match replay.save(&path) {
+            Ok(()) => println!("Saved replay to {}.", path),
+            Err(e) => println!("Failed to save replay to {}: {:?}", path, e),
+        }
     }
 }
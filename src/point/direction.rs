@@ -3,7 +3,7 @@ use std::fmt;
 use std::slice::Iter;
 
 use point;
-use rand::{self, Rng};
+use rand::Rng;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
@@ -127,8 +127,8 @@ impl Direction {
         }
     }
 
-    pub fn choose8() -> Direction {
-        *rand::thread_rng().choose(&DIRECTIONS).unwrap()
+    pub fn choose8<R: Rng>(rng: &mut R) -> Direction {
+        *rng.choose(&DIRECTIONS).unwrap()
     }
 
     pub fn iter8() -> Iter<'static, Direction> {
@@ -49,6 +49,49 @@ pub fn write_tile_atlas_config(config: &TileAtlasConfig, config_name: &str) {
     file.write_all(data.as_slice()).unwrap();
 }
 
+#[derive(Deserialize)]
+struct MapDef {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TileDef {
+    name: String,
+    map: String,
+    offset: [u32; 2],
+    count: [u32; 2],
+    size: [u32; 2],
+}
+
+#[derive(Deserialize)]
+struct ConfigDef {
+    maps: Vec<MapDef>,
+    tiles: Vec<TileDef>,
+}
+
+/// Checks everything `build_from_toml` would otherwise panic on.
+fn check_config(toml_str: &str) -> Result<(), String> {
+    let config: ConfigDef = toml_str.parse::<Value>()
+        .and_then(|v| v.try_into())
+        .map_err(|e| format!("{}", e))?;
+
+    for map in config.maps.iter() {
+        let file_path = format!("data/texture/{}", map.name);
+        image::open(&file_path).map_err(|e| format!("Can't load {}: {}", file_path, e))?;
+    }
+
+    for (i, tile) in config.tiles.iter().enumerate() {
+        if !config.maps.iter().any(|m| m.name == tile.map) {
+            return Err(format!("Tile {} uses map {}, which isn't in maps", tile.name, tile.map));
+        }
+        if config.tiles[..i].iter().any(|t| t.name == tile.name && t.map == tile.map) {
+            return Err(format!("Tile {} is defined twice in map {}", tile.name, tile.map));
+        }
+    }
+
+    Ok(())
+}
+
 fn hash_str(s: &str) -> String {
     let mut hasher = Sha3::sha3_256();
     hasher.input_str(s);
@@ -96,6 +139,16 @@ impl TileAtlas {
         TileAtlas::new(cached_config, textures)
     }
 
+    /// Repacks the atlas from the config and textures on disk, ignoring the cache. The config is
+    /// checked first, so a broken one gives an error instead of a panic.
+    pub fn rebuild_from_config<F: Facade>(display: &F, filename: &str) -> Result<Self, String> {
+        let toml_str = util::read_string_checked(filename)?;
+        check_config(&toml_str).map_err(|e| format!("{}: {}", filename, e))?;
+
+        let packed_folder = Path::new(filename).file_stem().unwrap().to_str().unwrap();
+        Ok(TileAtlas::build_from_toml(display, packed_folder, &toml_str))
+    }
+
     fn build_from_toml<F: Facade>(display: &F, packed_folder: &str, toml_str: &str) -> Self {
         println!("Rebuilding tile atlas config \"{}\"", packed_folder);
 
@@ -4,7 +4,7 @@ mod util;
 mod traits;
 pub mod ui;
 
-pub use self::render::{RenderContext};
+pub use self::render::{RenderContext, SCREEN_WIDTH, SCREEN_HEIGHT, SPRITES_PATH};
 pub use self::traits::RenderUpdate;
 
 make_global!(RENDERER, RenderContext, RenderContext::new());
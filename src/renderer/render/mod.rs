@@ -1,6 +1,9 @@
 use std::time::{Duration, Instant};
 use std::thread;
 
+use calx_ecs::Entity;
+
+use ecs::query;
 use util;
 use world::World;
 
@@ -11,6 +14,7 @@ mod viewport;
 mod tilemap;
 
 use debug;
+use debug::console::ConsoleKey;
 use point;
 use self::background::Background;
 use self::spritemap::SpriteMap;
@@ -20,10 +24,12 @@ pub use self::viewport::Viewport;
 
 use engine::MouseState;
 use renderer::RenderUpdate;
+use renderer::atlas::TileAtlas;
 use renderer::ui::*;
 
 use glium;
 use glium::glutin;
+use glium::glutin::VirtualKeyCode;
 use glium::Surface;
 use glium::backend::Facade;
 use glium::index::PrimitiveType;
@@ -37,6 +43,8 @@ pub struct Vertex {
 
 implement_vertex!(Vertex, position);
 
+pub const SPRITES_PATH: &str = "data/sprites.toml";
+
 pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 1, 3, 2];
 pub const QUAD: [Vertex; 4] = [
     Vertex { position: [0, 1] },
@@ -171,6 +179,7 @@ impl RenderContext {
 
         let mut imgui = imgui::ImGui::init();
         imgui.set_ini_filename(None);
+        configure_imgui_keys(&mut imgui);
         let renderer = imgui_glium_renderer::Renderer::init(&mut imgui, &display).expect("Failed to initialize renderer");
 
         let accumulator = FpsAccumulator::new();
@@ -193,20 +202,40 @@ impl RenderContext {
         }
     }
 
-    pub fn update(&mut self, world: &World) {
+    pub fn update(&mut self, world: &World, alpha: f32) {
+        profile_scope!("render_update");
+        debug::update(world);
         if let Some(text) = debug::pop_text() {
             self.ui.set_text(text);
         }
 
         self.viewport.rot = world.camera_rot();
 
-        self.tilemap.update(world, &self.viewport);
-        self.spritemap.update(world, &self.viewport);
-        self.primitives.update(world, &self.viewport);
-        self.ui.update(world, &self.viewport);
+        {
+            profile_scope!("tilemap_update");
+            self.tilemap.update(world, &self.viewport, alpha);
+        }
+        {
+            profile_scope!("spritemap_update");
+            self.spritemap.update(world, &self.viewport, alpha);
+        }
+        {
+            profile_scope!("primitives_update");
+            self.primitives.update(world, &self.viewport, alpha);
+        }
+        self.ui.update(world, &self.viewport, alpha);
     }
 
-    pub fn render(&mut self) {
+    pub fn render(&mut self, world: &mut World) {
+        profile_scope!("render");
+
+        // Picks are resolved a frame late, since imgui holds on to `self` while it draws.
+        if let Some((x, y)) = debug::take_pick() {
+            let scale = self.imgui.display_framebuffer_scale();
+            let picked = self.entity_at(world, (x * scale.0, y * scale.1));
+            debug::follow_entity(picked);
+        }
+
         let mut target = self.backend.draw();
         target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
 
@@ -215,11 +244,17 @@ impl RenderContext {
         self.background
             .render(&self.backend, &mut target, &self.viewport, millis);
 
-        self.tilemap.redraw(&self.backend, millis);
+        {
+            profile_scope!("tilemap_redraw");
+            self.tilemap.redraw(&self.backend, millis);
+        }
         self.tilemap
             .render(&self.backend, &mut target, &self.viewport, millis);
 
-        self.spritemap.redraw(&self.backend, millis);
+        {
+            profile_scope!("spritemap_redraw");
+            self.spritemap.redraw(&self.backend, millis);
+        }
         self.spritemap
             .render(&self.backend, &mut target, &self.viewport, millis);
 
@@ -237,13 +272,44 @@ impl RenderContext {
 
         self.accumulator.fps.map(debug::set_fps);
 
-        debug::run_ui(&ui);
-
-        self.imgui_renderer.render(&mut target, ui).expect("Rendering failed");
+        {
+            profile_scope!("imgui");
+            debug::run_ui(&ui, world);
+            self.imgui_renderer.render(&mut target, ui).expect("Rendering failed");
+        }
 
+        profile_scope!("swap_buffers");
         target.finish().unwrap();
     }
 
+    /// The entity whose sprite is drawn nearest to a point on the screen, in framebuffer pixels.
+    pub fn entity_at(&self, world: &World, screen_pos: (f32, f32)) -> Option<Entity> {
+        const PICK_RADIUS: f32 = 64.0;
+
+        let camera = world.camera_pos().unwrap_or(point::zero());
+        let (w, h) = self.backend.get_framebuffer_dimensions();
+
+        let mut nearest: Option<(Entity, f32)> = None;
+        for (entity, pos) in query::iter(&world.ecs().positions) {
+            if !world.ecs().appearances.contains(entity) {
+                continue;
+            }
+
+            // The same coordinates `spritemap` gives the sprite.
+            let map_coord = [(pos.pos.x - camera.x) * 64.0, (pos.pos.z - camera.z) * 64.0, pos.pos.y * 64.0];
+            let (x, y) = self.viewport.project_sprite(map_coord);
+            let x = (x + 1.0) / 2.0 * w as f32;
+            let y = (1.0 - y) / 2.0 * h as f32;
+
+            let dist = ((x - screen_pos.0).powi(2) + (y - screen_pos.1).powi(2)).sqrt();
+            if dist < nearest.map_or(PICK_RADIUS, |(_, d)| d) {
+                nearest = Some((entity, dist));
+            }
+        }
+
+        nearest.map(|(entity, _)| entity)
+    }
+
     pub fn set_viewport(&mut self, w: u32, h: u32) {
         let scale = self.backend.gl_window().hidpi_factor();
         self.viewport = Viewport {
@@ -265,9 +331,10 @@ impl RenderContext {
     }
 
     pub fn step_frame(&mut self) {
-        self.accumulator.step_frame();
-
+        profile_scope!("sleep");
         thread::sleep(self.accumulator.sleep_time());
+
+        self.accumulator.step_frame();
     }
 
     pub fn reload_shaders(&mut self) {
@@ -275,6 +342,49 @@ impl RenderContext {
         self.spritemap.reload_shaders(&self.backend);
     }
 
+    /// Repacks the sprite atlas from `data/sprites.toml` and the textures it names. If that fails,
+    /// the error is logged and the old atlas stays.
+    pub fn reload_atlas(&mut self) {
+        match TileAtlas::rebuild_from_config(&self.backend, SPRITES_PATH) {
+            Ok(atlas) => {
+                self.spritemap.set_atlas(atlas);
+                // The rebuild refreshed the cache, so this doesn't pack everything a second time.
+                self.tilemap.set_atlas(TileAtlas::from_config(&self.backend, SPRITES_PATH));
+            },
+            Err(e) => log!("Couldn't reload sprites: {}", e),
+        }
+    }
+
+    /// Passes a key to imgui, and to the console if it is one the console uses.
+    pub fn set_key(&mut self, code: VirtualKeyCode, pressed: bool) {
+        if let Some(index) = IMGUI_KEYS.iter().position(|&(c, _)| c == code) {
+            self.imgui.set_key(index as u8, pressed);
+        }
+
+        match code {
+            VirtualKeyCode::LControl | VirtualKeyCode::RControl => self.imgui.set_key_ctrl(pressed),
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => self.imgui.set_key_shift(pressed),
+            VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => self.imgui.set_key_alt(pressed),
+            _ => (),
+        }
+
+        if pressed {
+            let console_key = match code {
+                VirtualKeyCode::Tab => Some(ConsoleKey::Complete),
+                VirtualKeyCode::Up => Some(ConsoleKey::Previous),
+                VirtualKeyCode::Down => Some(ConsoleKey::Next),
+                _ => None,
+            };
+            if let Some(key) = console_key {
+                debug::console_key(key);
+            }
+        }
+    }
+
+    pub fn add_input_character(&mut self, ch: char) {
+        self.imgui.add_input_character(ch);
+    }
+
     pub fn set_mouse(&mut self, mouse_state: &mut MouseState) {
         let scale = self.imgui.display_framebuffer_scale();
         self.imgui.set_mouse_pos(
@@ -299,6 +409,34 @@ impl RenderContext {
     }
 }
 
+const IMGUI_KEYS: [(VirtualKeyCode, imgui::ImGuiKey); 19] = [
+    (VirtualKeyCode::Tab, imgui::ImGuiKey::Tab),
+    (VirtualKeyCode::Left, imgui::ImGuiKey::LeftArrow),
+    (VirtualKeyCode::Right, imgui::ImGuiKey::RightArrow),
+    (VirtualKeyCode::Up, imgui::ImGuiKey::UpArrow),
+    (VirtualKeyCode::Down, imgui::ImGuiKey::DownArrow),
+    (VirtualKeyCode::PageUp, imgui::ImGuiKey::PageUp),
+    (VirtualKeyCode::PageDown, imgui::ImGuiKey::PageDown),
+    (VirtualKeyCode::Home, imgui::ImGuiKey::Home),
+    (VirtualKeyCode::End, imgui::ImGuiKey::End),
+    (VirtualKeyCode::Delete, imgui::ImGuiKey::Delete),
+    (VirtualKeyCode::Back, imgui::ImGuiKey::Backspace),
+    (VirtualKeyCode::Return, imgui::ImGuiKey::Enter),
+    (VirtualKeyCode::Escape, imgui::ImGuiKey::Escape),
+    (VirtualKeyCode::A, imgui::ImGuiKey::A),
+    (VirtualKeyCode::C, imgui::ImGuiKey::C),
+    (VirtualKeyCode::V, imgui::ImGuiKey::V),
+    (VirtualKeyCode::X, imgui::ImGuiKey::X),
+    (VirtualKeyCode::Y, imgui::ImGuiKey::Y),
+    (VirtualKeyCode::Z, imgui::ImGuiKey::Z),
+];
+
+fn configure_imgui_keys(imgui: &mut imgui::ImGui) {
+    for (index, &(_, key)) in IMGUI_KEYS.iter().enumerate() {
+        imgui.set_imgui_key(key, index as u8);
+    }
+}
+
 pub trait Renderable {
     fn render<F, S>(&self, display: &F, target: &mut S, viewport: &Viewport, time: u64)
         where
@@ -306,15 +444,16 @@ pub trait Renderable {
         S: glium::Surface;
 }
 
+/// Measures how long each rendered frame takes. The simulation's fixed timestep is handled
+/// separately by `engine::timestep::FixedTimestep`.
 pub struct FpsAccumulator {
     start: Instant,
     frame_count: u32,
     last_time: u64,
-    accumulator: Duration,
     previous_clock: Instant,
     delta: f32,
     pub fps: Option<f32>,
-    fixed_time_stamp: Duration,
+    frame_time: Duration,
 }
 
 impl FpsAccumulator {
@@ -323,25 +462,19 @@ impl FpsAccumulator {
             start: Instant::now(),
             frame_count: 0,
             last_time: 0,
-            accumulator: Duration::new(0, 0),
             previous_clock: Instant::now(),
             delta: 0.0,
             fps: None,
-            fixed_time_stamp: Duration::new(0, 16666667),
+            frame_time: Duration::new(0, 16666667),
         }
     }
 
     pub fn step_frame(&mut self) {
         let now = Instant::now();
         let delta = now - self.previous_clock;
-        self.accumulator += delta;
         self.delta = delta.as_secs() as f32 + delta.subsec_nanos() as f32 / 1_000_000_000.0;
         self.previous_clock = now;
 
-        while self.accumulator >= self.fixed_time_stamp {
-            self.accumulator -= self.fixed_time_stamp;
-        }
-
         let millis = ::util::get_duration_millis(&Instant::now().duration_since(self.start));
 
         if millis - self.last_time >= 1000 {
@@ -365,8 +498,10 @@ impl FpsAccumulator {
         1000.0 / self.ms_per_frame()
     }
 
+    /// The time left in this frame, so rendering is capped at about 60 frames per second.
     pub fn sleep_time(&self) -> Duration {
-        Duration::new(0, 16666667) - self.accumulator
+        let elapsed = Instant::now().duration_since(self.previous_clock);
+        self.frame_time.checked_sub(elapsed).unwrap_or(Duration::new(0, 0))
     }
 
     pub fn millis_since_start(&self) -> u64 {
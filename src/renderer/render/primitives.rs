@@ -108,8 +108,8 @@ impl<'a> Renderable for Primitives {
 }
 
 use renderer::RenderUpdate;
-use world::World;
-use ecs::traits::ComponentQuery;
+use world::{World, BLOCK_HALF_HEIGHT};
+use ecs::query;
 use ecs::components::{Appearance, PhysicsShape};
 use point::Direction;
 
@@ -118,29 +118,28 @@ impl RenderUpdate for Primitives {
         true
     }
 
-    fn update(&mut self, world: &World, viewport: &Viewport) {
+    fn update(&mut self, world: &World, viewport: &Viewport, alpha: f32) {
         let mut instances = Vec::new();
         let mut verts = Vec::new();
-        let camera = world.camera_pos().unwrap_or(point::zero());
+        let camera = world.camera_pos_interpolated(alpha).unwrap_or(point::zero());
         let min = viewport.min_point((camera.x, camera.z), 32);
-        for entity in world.entities() {
-            if world.ecs().physics.has(*entity) {
-                let pos = world.ecs().positions.get_or_err(*entity);
-
-                let scale = match world.ecs().physics.get_or_err(*entity).shape {
-                    PhysicsShape::Chara => [1.0, 1.0, 1.0],
-                    PhysicsShape::Wall => [1.0, 1.0, 20.0],
-                    PhysicsShape::Bullet => [0.3, 0.3, 0.3],
-                };
-
-                instances.push(Instance {
-                    offset: [pos.pos.x - camera.x + 0.5, pos.pos.z - camera.z + 0.5, pos.pos.y],
-                    scale: scale,
-                });
-
-                verts.push(Vertex3f { position: [pos.pos.x - camera.x, pos.pos.z - camera.z, pos.pos.y] });
-                verts.push(Vertex3f { position: [pos.pos.x - camera.x + 1.0, pos.pos.z - camera.z + 1.0, pos.pos.y] });
-            }
+        for (_, phys, pos) in query::join2(&world.ecs().physics, &world.ecs().positions) {
+            let pos = pos.interpolated(alpha);
+
+            let scale = match phys.shape {
+                PhysicsShape::Chara => [1.0, 1.0, 1.0],
+                PhysicsShape::Wall => [1.0, 1.0, 20.0],
+                PhysicsShape::Block => [1.0, 1.0, 2.0 * BLOCK_HALF_HEIGHT],
+                PhysicsShape::Bullet => [0.3, 0.3, 0.3],
+            };
+
+            instances.push(Instance {
+                offset: [pos.x - camera.x + 0.5, pos.z - camera.z + 0.5, pos.y],
+                scale: scale,
+            });
+
+            verts.push(Vertex3f { position: [pos.x - camera.x, pos.z - camera.z, pos.y] });
+            verts.push(Vertex3f { position: [pos.x - camera.x + 1.0, pos.z - camera.z + 1.0, pos.y] });
         }
 
         for (pos, blocked) in world.grid.nodes.iter() {
@@ -37,7 +37,7 @@ struct DrawSprite {
 
 impl SpriteMap {
     pub fn new<F: Facade>(display: &F) -> Self {
-        let tile_atlas = TileAtlas::from_config(display, "data/sprites.toml");
+        let tile_atlas = TileAtlas::from_config(display, render::SPRITES_PATH);
 
         let (vertices, indices) = render::make_quad_buffers(display);
 
@@ -64,6 +64,11 @@ impl SpriteMap {
         }
     }
 
+    pub fn set_atlas(&mut self, tile_atlas: TileAtlas) {
+        self.tile_atlas = tile_atlas;
+        self.valid = false;
+    }
+
     fn make_instances<F>(&mut self, display: &F)
         where F: glium::backend::Facade {
 
@@ -89,7 +94,7 @@ impl SpriteMap {
                     let tile_idx = self.tile_atlas.get_tile_index(&sprite.kind);
 
                     Instance { tile_idx: tile_idx,
-                               map_coord: [x, z, y * 2],
+                               map_coord: [x, z, y],
                                inner_offset: [ix, iy],
                                tex_offset: [tx, ty],
                                tex_ratio: tex_ratio,
@@ -141,7 +146,7 @@ impl<'a> Renderable for SpriteMap {
 
 use renderer::RenderUpdate;
 use world::World;
-use ecs::traits::ComponentQuery;
+use ecs::query;
 use ecs::components::Appearance;
 use point::Direction;
 
@@ -153,22 +158,22 @@ const HAIR_COUNT: u32 = 28;
 const EAR_COUNT: u32 = 10;
 const FACE_COUNT: u32 = 9;
 
-fn make_sprites(world: &World, viewport: &Viewport) -> Vec<(DrawSprite, (i32, i32, i32, i32, i32))> {
+fn make_sprites(world: &World, viewport: &Viewport, alpha: f32) -> Vec<(DrawSprite, (i32, i32, i32, i32, i32))> {
     let mut res = Vec::new();
 
-    let camera = world.camera_pos().unwrap_or(point::zero());
+    let camera = world.camera_pos_interpolated(alpha).unwrap_or(point::zero());
     let min = viewport.min_point((camera.x, camera.z), 1);
     {
-        for entity in world.entities() {
-            if !world.ecs().positions.has(*entity) {
+        for (entity, pos) in query::iter(&world.ecs().positions) {
+            if world.is_stowed(entity) {
                 continue;
             }
 
-            let pos = world.ecs().positions.get_or_err(*entity);
             let ord = pos.cardinal_dir().ordinal() as u32;
-            let screen_x = (pos.pos.x * 64.0) as i32;
-            let screen_y = (pos.pos.y) as i32;
-            let screen_z = (pos.pos.z * 64.0) as i32;
+            let draw_pos = pos.interpolated(alpha);
+            let screen_x = (draw_pos.x * 64.0) as i32;
+            let screen_y = (draw_pos.y * 64.0) as i32;
+            let screen_z = (draw_pos.z * 64.0) as i32;
 
             let mut push_sprite = |variant: u32, pos: (i32, i32), kind: &str| {
                 let sprite = DrawSprite { kind: kind.to_string(), variant: variant };
@@ -178,9 +183,9 @@ fn make_sprites(world: &World, viewport: &Viewport) -> Vec<(DrawSprite, (i32, i3
                 res.push((sprite, (x, y, z, pos.0, pos.1)));
             };
 
-            match world.ecs().appearances.get(*entity) {
+            match world.ecs().appearances.get(entity) {
                 Some(&Appearance::Chara(ref chara)) => {
-                    let phys = world.ecs().physics.get_or_err(*entity);
+                    let movement_frames = world.ecs().physics.get(entity).map_or(0, |p| p.movement_frames);
                     let tail_occluded = pos.cardinal_dir() != Direction::N &&
                         pos.cardinal_dir() != Direction::NE &&
                         pos.cardinal_dir() != Direction::NW;
@@ -192,7 +197,7 @@ fn make_sprites(world: &World, viewport: &Viewport) -> Vec<(DrawSprite, (i32, i3
                     let ear_kind = (chara.ear_kind % EAR_COUNT) + ord * EAR_COUNT;
                     let face_kind = (chara.face_kind % FACE_COUNT) + ord * FACE_COUNT;
 
-                    let add_pos = if phys.movement_frames / 4 == 1 {
+                    let add_pos = if movement_frames / 4 == 1 {
                         -1
                     } else {
                         0
@@ -205,8 +210,8 @@ fn make_sprites(world: &World, viewport: &Viewport) -> Vec<(DrawSprite, (i32, i3
                     push_sprite(body_kind, (0 + add_pos, 0), "body");
 
                     // TODO: move to movement logic
-                    if phys.movement_frames != 0 {
-                        feet_kind += ((phys.movement_frames / 5) % 6) + 1;
+                    if movement_frames != 0 {
+                        feet_kind += ((movement_frames / 5) % 6) + 1;
                     }
                     push_sprite(feet_kind, (0, 64), "feet");
                     push_sprite(jacket_kind, (0 + add_pos,  -10), "jacket");
@@ -240,8 +245,8 @@ impl RenderUpdate for SpriteMap {
         true
     }
 
-    fn update(&mut self, world: &World, viewport: &Viewport) {
-        self.sprites = make_sprites(world, viewport);
+    fn update(&mut self, world: &World, viewport: &Viewport, alpha: f32) {
+        self.sprites = make_sprites(world, viewport, alpha);
         self.valid = false;
     }
 
@@ -35,7 +35,7 @@ use point::Direction::*;
 
 impl TileMap {
     pub fn new<F: Facade>(display: &F) -> Self {
-        let tile_atlas = TileAtlas::from_config(display, "data/sprites.toml");
+        let tile_atlas = TileAtlas::from_config(display, render::SPRITES_PATH);
 
         let (vertices, indices) = render::make_quad_buffers(display);
 
@@ -56,6 +56,11 @@ impl TileMap {
         tilemap
     }
 
+    pub fn set_atlas(&mut self, tile_atlas: TileAtlas) {
+        self.tile_atlas = tile_atlas;
+        self.valid = false;
+    }
+
     fn make_instances<F>(&mut self, display: &F, msecs: u64)
         where F: glium::backend::Facade {
 
@@ -152,13 +157,13 @@ impl RenderUpdate for TileMap {
         true
     }
 
-    fn update(&mut self, world: &World, viewport: &Viewport) {
+    fn update(&mut self, world: &World, viewport: &Viewport, alpha: f32) {
         self.valid = self.tiles.len() == world.tiles.len();
         if !self.valid {
             self.tiles = make_map(world, viewport);
             self.instances.clear();
         }
-        let camera = world.camera_pos().unwrap_or(point::zero());
+        let camera = world.camera_pos_interpolated(alpha).unwrap_or(point::zero());
         self.camera = (camera.x, camera.z);
     }
 
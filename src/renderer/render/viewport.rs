@@ -1,4 +1,4 @@
-use cgmath;
+use cgmath::{self, Matrix, Matrix4, Vector4};
 use glium;
 
 #[derive(Debug)]
@@ -89,6 +89,28 @@ impl Viewport {
         cgmath::ortho(x - half_width, x + half_width, y + half_height, y - half_height, -100.0, 100.0).into()
     }
 
+    /// Where `sprite.vert` puts a sprite's origin, in normalized device coordinates. `map_coord`
+    /// is the same as the sprite instance's.
+    pub fn project_sprite(&self, map_coord: [f32; 3]) -> (f32, f32) {
+        let matrix = Matrix4::from(self.camera_projection(1));
+        let (sin_z, cos_z) = self.rot.sin_cos();
+        let rotate_z = Matrix4::new(cos_z, sin_z, 0.0, 0.0,
+                                    -sin_z, cos_z, 0.0, 0.0,
+                                    0.0, 0.0, 0.0, 0.0,
+                                    0.0, 0.0, 0.0, 1.0);
+        let (sin_x, cos_x) = 0.785398f32.sin_cos();
+        let rotate_x = Matrix4::new(1.0, 0.0, 0.0, 0.0,
+                                    0.0, cos_x, sin_x, 0.0,
+                                    0.0, -sin_x, cos_x, 0.0,
+                                    0.0, 0.0, 0.0, 1.0);
+
+        // The shader multiplies with the vector on the left, which is the same as multiplying
+        // the transpose with it on the right.
+        let coord = Vector4::new(map_coord[0], map_coord[1], map_coord[2], 1.0);
+        let pos = (matrix * rotate_z * rotate_x).transpose() * coord;
+        (pos.x, pos.y)
+    }
+
     fn scissor(&self, area: (u32, u32, u32, u32)) -> glium::Rect {
         let (ax, ay, aw, ah) = area;
         let (_, h) = self.scaled_size();
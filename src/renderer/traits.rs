@@ -4,7 +4,8 @@ use renderer::render::Viewport;
 
 pub trait RenderUpdate {
     fn should_update(&self, world: &World) -> bool;
-    fn update(&mut self, world: &World, viewport: &Viewport);
+    /// `alpha` is how far between the last two ticks to draw things, from 0 to 1.
+    fn update(&mut self, world: &World, viewport: &Viewport, alpha: f32);
 
     fn redraw<F: Facade>(&mut self, _display: &F, _msecs: u64) {}
 }
@@ -150,7 +150,7 @@ impl RenderUpdate for Ui {
         true
     }
 
-    fn update(&mut self, world: &World, _viewport: &Viewport) {
+    fn update(&mut self, world: &World, _viewport: &Viewport, _alpha: f32) {
         self.invalidate();
         self.redraw();
     }
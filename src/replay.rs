@@ -0,0 +1,100 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use bincode;
+
+use GameContext;
+use engine::FrameInput;
+use headless::InputSource;
+use world::SaveResult;
+
+/// A recorded session. Replaying the frames against a world made from the same seed reproduces the
+/// session, as long as the debug variables it was recorded with are unchanged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub frames: Vec<FrameInput>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed: seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, input: &FrameInput) {
+        self.frames.push(input.clone());
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> SaveResult<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, self, bincode::Infinite)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> SaveResult<Replay> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let replay = bincode::deserialize_from(&mut reader, bincode::Infinite)?;
+        Ok(replay)
+    }
+
+    pub fn into_player(self) -> ReplayPlayer {
+        ReplayPlayer {
+            replay: self,
+            pos: 0,
+        }
+    }
+}
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    pos: usize,
+}
+
+impl ReplayPlayer {
+    pub fn next(&mut self) -> Option<FrameInput> {
+        let input = self.replay.frames.get(self.pos).cloned();
+        if input.is_some() {
+            self.pos += 1;
+        }
+        input
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pos >= self.replay.frames.len()
+    }
+}
+
+impl InputSource for ReplayPlayer {
+    fn next_frame(&mut self, _frame: u64, _context: &GameContext) -> Option<FrameInput> {
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+    use engine::keys::KeyCode;
+
+    #[test]
+    fn test_round_trip() {
+        let path = env::temp_dir().join("potato-dan-test.replay");
+        let mut replay = Replay::new(42);
+        replay.record(&FrameInput::new(0.016).press(KeyCode::W));
+        replay.record(&FrameInput::new(0.017));
+        replay.save(&path).unwrap();
+
+        let mut player = Replay::load(&path).unwrap().into_player();
+        assert_eq!(player.next().map(|i| i.keys.get(&KeyCode::W).cloned()), Some(Some(true)));
+        assert_eq!(player.next().map(|i| i.delta), Some(0.017));
+        assert!(player.is_finished());
+    }
+}
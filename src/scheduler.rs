@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use debug;
+use debug::profile;
+use world::World;
+
+/// Timing information passed to every system.
+#[derive(Clone, Copy, Debug)]
+pub struct Tick {
+    pub frame: u64,
+    pub delta: f32,
+}
+
+pub type SystemFn = fn(&mut World, &Tick);
+
+pub struct System {
+    pub name: &'static str,
+
+    /// The ECS storages this system looks at or changes, by field name. Changing includes spawning
+    /// and removing entities. Used to warn about systems that touch the same components, at least
+    /// one of them changing them, without an ordering between them.
+    pub reads: &'static [&'static str],
+    pub writes: &'static [&'static str],
+
+    /// Systems that must run before this one.
+    pub after: &'static [&'static str],
+
+    pub run: SystemFn,
+}
+
+pub struct Scheduler {
+    systems: Vec<System>,
+    order: Vec<usize>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            systems: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, system: System) {
+        assert!(self.systems.iter().all(|s| s.name != system.name),
+                "System {} was added twice",
+                system.name);
+        debug::register_system(system.name);
+        self.systems.push(system);
+        self.order = self.sort();
+
+        for (a, b, component) in self.conflicts() {
+            log!("Systems {} and {} share {} and one writes it, but they aren't ordered",
+                 a, b, component);
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.order.iter().map(|&i| self.systems[i].name).collect()
+    }
+
+    /// Runs every enabled system once, in dependency order.
+    pub fn run(&self, world: &mut World, tick: &Tick) {
+        for &idx in self.order.iter() {
+            let system = &self.systems[idx];
+            if !debug::system_enabled(system.name) {
+                continue;
+            }
+
+            let scope = profile::scope(system.name);
+            (system.run)(world, tick);
+            debug::set_system_time(system.name, scope.end());
+        }
+    }
+
+    /// Orders the systems so each one runs after everything in its `after` list. Ties keep the
+    /// order the systems were added in. Constraints naming systems that weren't added are ignored,
+    /// so they can be added later.
+    fn sort(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+
+        while order.len() < self.systems.len() {
+            let next = self.systems.iter().position(|system| {
+                !done.contains(&system.name) &&
+                    system.after.iter().all(|dep| {
+                        done.contains(dep) || !self.systems.iter().any(|s| s.name == *dep)
+                    })
+            });
+
+            match next {
+                Some(i) => {
+                    done.insert(self.systems[i].name);
+                    order.push(i);
+                },
+                None => {
+                    let stuck: Vec<&str> = self.systems.iter()
+                                               .map(|s| s.name)
+                                               .filter(|n| !done.contains(n))
+                                               .collect();
+                    panic!("Cycle in system ordering between {:?}", stuck);
+                },
+            }
+        }
+
+        order
+    }
+
+    fn runs_before(&self, a: usize, b: usize) -> bool {
+        let mut stack = vec![b];
+        let mut seen = HashSet::new();
+        while let Some(idx) = stack.pop() {
+            if !seen.insert(idx) {
+                continue;
+            }
+            for dep in self.systems[idx].after.iter() {
+                if let Some(d) = self.systems.iter().position(|s| s.name == *dep) {
+                    if d == a {
+                        return true;
+                    }
+                    stack.push(d);
+                }
+            }
+        }
+        false
+    }
+
+    /// Pairs of systems that run in no particular order while one of them writes a storage the
+    /// other reads or writes, and what that storage is.
+    pub fn conflicts(&self) -> Vec<(&'static str, &'static str, &'static str)> {
+        let mut res = Vec::new();
+        for i in 0..self.systems.len() {
+            for j in (i + 1)..self.systems.len() {
+                if self.runs_before(i, j) || self.runs_before(j, i) {
+                    continue;
+                }
+                let (a, b) = (&self.systems[i], &self.systems[j]);
+                let uses = |system: &System, component: &str| {
+                    system.reads.iter().chain(system.writes.iter()).any(|c| *c == component)
+                };
+                let mut shared: Vec<&'static str> = a.writes.iter()
+                    .filter(|c| uses(b, c))
+                    .chain(b.writes.iter().filter(|c| uses(a, c)))
+                    .cloned()
+                    .collect();
+                shared.sort();
+                shared.dedup();
+                for component in shared {
+                    res.push((a.name, b.name, component));
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nothing(_world: &mut World, _tick: &Tick) {}
+
+    fn system(name: &'static str, after: &'static [&'static str]) -> System {
+        System {
+            name: name,
+            reads: &[],
+            writes: &[],
+            after: after,
+            run: nothing,
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(system("c", &["b"]));
+        scheduler.add(system("b", &["a"]));
+        scheduler.add(system("a", &[]));
+        scheduler.add(system("d", &[]));
+        assert_eq!(scheduler.names(), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(System { writes: &["positions"], ..system("move", &[]) });
+        scheduler.add(System { reads: &["positions"], ..system("look", &[]) });
+        scheduler.add(System { reads: &["positions"], ..system("after", &["move"]) });
+        assert_eq!(scheduler.conflicts(), vec![("move", "look", "positions")]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cycle() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(system("a", &["b"]));
+        scheduler.add(system("b", &["a"]));
+    }
+}
@@ -1,47 +1,57 @@
 use std::collections::{HashMap, HashSet};
 
 use GameContext;
-use ai::{self, Ai, AiKind, Action};
+use ai::{self, Action};
 use calx_ecs::Entity;
 use debug;
 use engine::MouseState;
 use ecs::Loadout;
+use ecs::ballistics::{self, Splash};
 use ecs::components::*;
 use ecs::prefab;
+use ecs::query;
 use ecs::traits::*;
+use engine::bindings::{self, InputAction};
 use engine::keys::KeyCode;
+use lua;
 use point::*;
 use point;
-use rand::{self, Rng};
+use rand::Rng;
 use renderer;
+use scheduler::{Scheduler, System, Tick};
 use util;
 use world::{self, World, Event};
+use world::rng::RngStream;
 
 pub struct GameState {
     pub frame: u64,
     pub world: World,
+    /// The input actions held last frame, for telling when one was just pressed.
+    pub held_actions: HashSet<InputAction>,
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    pub fn new(seed: u64) -> Self {
         let siz = debug::get("world_size") as u32;
         let w = siz;
         let h = siz;
-        let mut world = World::new(w, h);
+        let mut world = World::new(w, h, seed);
 
         for i in 0..debug::get("charas") as u32 {
-            let x = rand::thread_rng().gen_range(1.0, (w - 1) as f32);
-            let z = rand::thread_rng().gen_range(1.0, (h - 1) as f32);
-            let mob = world.spawn(prefab::mob("Dood").c(Ai::new(AiKind::SeekTarget)), Point::new(x, 0.0, z)).unwrap();
-            let gun = world.spawn(prefab::gun(), point::zero()).unwrap();
-            world.equip(mob, gun);
+            let x = world.rng(RngStream::Spawn).gen_range(1.0, (w - 1) as f32);
+            let z = world.rng(RngStream::Spawn).gen_range(1.0, (h - 1) as f32);
+            world.spawn_prefab("dood", Point::new(x, 0.0, z));
         }
 
         world::gen::city(&mut world);
 
+        lua::reset();
+        lua::run_hooks(&mut world, "start", "");
+
         GameState {
             frame: 0,
             world: world,
+            held_actions: HashSet::new(),
         }
     }
 }
@@ -51,20 +61,39 @@ pub enum Command {
     Move(Direction),
     Jump,
     Shoot,
+    Reload,
+    Melee,
+    PickUp,
+    DropWeapon,
+    CycleWeapon(i32),
     Wait,
     Quit,
     ReloadShaders,
     Restart,
     Bom,
     RotateCamera(f32),
+    QuickSave,
+    QuickLoad,
 }
 
-pub fn get_commands(input: &HashMap<KeyCode, bool>) -> Vec<Command> {
+const QUICK_SAVE_PATH: &str = "save/quick.sav";
+
+/// Turns the input into commands. `previous` holds the actions that were held last frame, and is
+/// updated to the ones held now.
+pub fn get_commands(input: &HashMap<KeyCode, bool>, mouse: &MouseState,
+                    previous: &mut HashSet<InputAction>) -> Vec<Command> {
     let mut commands = Vec::new();
-    let a = input.get(&KeyCode::A).map_or(false, |b| *b);
-    let w = input.get(&KeyCode::W).map_or(false, |b| *b);
-    let s = input.get(&KeyCode::S).map_or(false, |b| *b);
-    let d = input.get(&KeyCode::D).map_or(false, |b| *b);
+    let actions = bindings::with(|b| b.active_actions(input, mouse));
+    let just_pressed: HashSet<InputAction> = actions.difference(previous).cloned().collect();
+    *previous = actions.clone();
+
+    let held = |action: InputAction| actions.contains(&action);
+    let pressed = |action: InputAction| just_pressed.contains(&action);
+
+    let a = held(InputAction::MoveLeft);
+    let w = held(InputAction::MoveUp);
+    let s = held(InputAction::MoveDown);
+    let d = held(InputAction::MoveRight);
 
     if a && s {
         commands.push(Command::Move(Direction::SW));
@@ -94,37 +123,60 @@ pub fn get_commands(input: &HashMap<KeyCode, bool>) -> Vec<Command> {
         commands.push(Command::Wait);
     }
 
-    let space = input.get(&KeyCode::Space).map_or(false, |b| *b);
-    if space {
+    if held(InputAction::Jump) {
         commands.push(Command::Jump);
     }
 
-    let r = input.get(&KeyCode::R).map_or(false, |b| *b);
-    if r {
+    if held(InputAction::ReloadShaders) {
         commands.push(Command::ReloadShaders);
     }
 
-    let f = input.get(&KeyCode::F).map_or(false, |b| *b);
-    if f {
+    if held(InputAction::Shoot) {
         commands.push(Command::Shoot);
     }
 
-    let z = input.get(&KeyCode::Z).map_or(false, |b| *b);
-    if z {
+    if held(InputAction::Reload) {
+        commands.push(Command::Reload);
+    }
+
+    if held(InputAction::Melee) {
+        commands.push(Command::Melee);
+    }
+
+    if pressed(InputAction::PickUp) {
+        commands.push(Command::PickUp);
+    }
+
+    if pressed(InputAction::DropWeapon) {
+        commands.push(Command::DropWeapon);
+    }
+
+    if pressed(InputAction::NextWeapon) {
+        commands.push(Command::CycleWeapon(1));
+    }
+    else if pressed(InputAction::PreviousWeapon) {
+        commands.push(Command::CycleWeapon(-1));
+    }
+
+    if held(InputAction::Restart) {
         commands.push(Command::Restart);
     }
 
-    let b = input.get(&KeyCode::B).map_or(false, |b| *b);
-    if b {
+    if held(InputAction::Bom) {
         commands.push(Command::Bom);
     }
 
-    let q = input.get(&KeyCode::Q).map_or(false, |b| *b);
-    let e = input.get(&KeyCode::E).map_or(false, |b| *b);
-    if q {
+    if held(InputAction::QuickSave) {
+        commands.push(Command::QuickSave);
+    }
+    else if held(InputAction::QuickLoad) {
+        commands.push(Command::QuickLoad);
+    }
+
+    if held(InputAction::RotateCameraLeft) {
         commands.push(Command::RotateCamera(-0.1));
     }
-    else if e {
+    else if held(InputAction::RotateCameraRight) {
         commands.push(Command::RotateCamera(0.1));
     }
 
@@ -139,7 +191,7 @@ pub fn game_step(context: &mut GameContext, input: &HashMap<KeyCode, bool>, mous
         return;
     }
 
-    for command in get_commands(input) {
+    for command in get_commands(input, mouse, &mut context.state.held_actions) {
         run_command(context, command, delta);
     }
 
@@ -149,189 +201,231 @@ pub fn game_step(context: &mut GameContext, input: &HashMap<KeyCode, bool>, mous
 }
 
 fn process(context: &mut GameContext, delta: f32) {
-    let poll = debug::get("poll") as u64;
-    let recheck = context.state.frame % poll == 0;
-
-    update_camera(context);
-
-    step_ai(&mut context.state.world, true, delta);
-    step_bomb(&mut context.state.world, delta);
-    context.state.world.update_physics(recheck);
-    step_physics(&mut context.state.world, delta);
-    step_holds(&mut context.state.world);
-    step_gun(&mut context.state.world);
-    step_bullet(&mut context.state.world, delta);
-    step_healths(&mut context.state.world);
+    profile_scope!("process");
+    let tick = Tick {
+        frame: context.state.frame,
+        delta: delta,
+    };
 
-    // TODO: move here
-    context.state.world.handle_events();
-    context.state.world.purge_dead();
+    context.state.world.store_last_positions();
+    instance::with(|scheduler| scheduler.run(&mut context.state.world, &tick));
 
     context.state.frame += 1;
 }
 
-fn step_physics(world: &mut World, delta: f32) {
-    let mut objects = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().physics.has(*entity) {
-            objects.push(*entity);
-        }
-    }
+fn make_scheduler() -> Scheduler {
+    let mut scheduler = Scheduler::new();
+    scheduler.add(System {
+        name: "camera",
+        reads: &["cameras", "positions"],
+        writes: &["positions"],
+        after: &[],
+        run: |world, _| update_camera(world),
+    });
+    scheduler.add(System {
+        name: "scripts",
+        reads: &["healths", "names", "positions"],
+        writes: &["positions", "physics", "holds"],
+        after: &["camera"],
+        run: |world, tick| lua::run_hooks(world, "tick", &tick.frame.to_string()),
+    });
+    scheduler.add(System {
+        name: "ai",
+        reads: &["ais", "charas", "guns", "healths", "holds", "inventories", "melees", "positions"],
+        writes: &["ais", "physics", "positions", "guns", "melees", "bullets"],
+        after: &["camera", "scripts"],
+        run: |world, tick| step_ai(world, true, tick.delta),
+    });
+    scheduler.add(System {
+        name: "bomb",
+        reads: &["bombs", "charas", "healths", "physics", "positions"],
+        writes: &["bombs", "physics"],
+        after: &["ai"],
+        run: |world, tick| step_bomb(world, tick.delta),
+    });
+    scheduler.add(System {
+        name: "collision",
+        reads: &["bullets", "charas", "healths", "physics", "positions"],
+        writes: &["bullets", "physics", "positions"],
+        after: &["bomb"],
+        run: step_collision,
+    });
+    scheduler.add(System {
+        name: "physics",
+        reads: &["physics", "positions"],
+        writes: &["physics", "positions"],
+        after: &["collision"],
+        run: |world, tick| step_physics(world, tick.delta),
+    });
+    scheduler.add(System {
+        name: "holds",
+        reads: &["holds"],
+        writes: &["holds"],
+        after: &["physics"],
+        run: |world, _| step_holds(world),
+    });
+    scheduler.add(System {
+        name: "melee",
+        reads: &["melees"],
+        writes: &["melees"],
+        after: &["ai"],
+        run: |world, tick| step_melee(world, tick.delta),
+    });
+    scheduler.add(System {
+        name: "gun",
+        reads: &["guns", "holds", "positions"],
+        writes: &["guns", "positions"],
+        after: &["holds"],
+        run: |world, tick| step_gun(world, tick.delta),
+    });
+    scheduler.add(System {
+        name: "bullet",
+        reads: &["bullets", "positions"],
+        writes: &["bullets"],
+        after: &["physics", "gun"],
+        run: |world, tick| step_bullet(world, tick.delta),
+    });
+    scheduler.add(System {
+        name: "healths",
+        reads: &["healths"],
+        writes: &[],
+        after: &["bullet", "gun"],
+        run: |world, _| step_healths(world),
+    });
+    scheduler.add(System {
+        name: "areas",
+        reads: &["charas", "positions"],
+        writes: &[],
+        after: &["gun"],
+        run: |world, _| step_areas(world),
+    });
+    scheduler.add(System {
+        name: "events",
+        reads: &["armors", "healths", "names", "physics"],
+        writes: &["healths", "physics"],
+        after: &["healths", "areas", "scripts"],
+        run: |world, _| world.handle_events(),
+    });
+    scheduler.add(System {
+        name: "purge",
+        reads: &["holds", "physics", "positions"],
+        writes: &[
+            "ais", "bombs", "bullets", "guns", "healths", "holds", "melees", "physics", "positions",
+        ],
+        after: &["events", "melee"],
+        run: |world, _| world.purge_dead(),
+    });
+    scheduler
+}
 
-    let mut groups = CollisionGroups::new();
+make_global!(SCHEDULER, Scheduler, make_scheduler());
 
-    let friction = debug::get("friction");
+fn step_collision(world: &mut World, tick: &Tick) {
+    let poll = debug::get("poll") as u64;
+    let recheck = tick.frame % poll == 0;
+    world.update_physics(recheck);
+}
 
-    for entity in objects {
-        let kind = world.ecs_mut().physics.get_mut_or_err(entity).kind;
-        let mut dx;
-        let mut dy;
-        let mut dz;
-        match kind {
+fn step_physics(world: &mut World, delta: f32) {
+    let gravity = debug::get("gravity");
+    let (w, h) = world.size();
+
+    let rests: HashMap<Entity, f32> = query::iter(&world.ecs().physics)
+        .filter(|&(_, phys)| falls(phys))
+        .map(|(entity, _)| (entity, world.resting_height(entity)))
+        .collect();
+
+    let ecs = world.ecs_mut();
+    query::each_mut2(&mut ecs.physics, &mut ecs.positions, |entity, phys, pos| {
+        match phys.kind {
+            PhysicsKind::Static => (),
             PhysicsKind::Physical => {
-                let mut set_to_ground = false;
-                {
-                    let pos = world.ecs().positions.get_or_err(entity).pos;
-                    let mut phys = world.ecs_mut().physics.get_mut_or_err(entity);
-
-                    let top_speed = debug::get("top_speed");
-
-                    let decel = 1.0 / (1.0 + (delta * friction));
-                    phys.vel += phys.accel * delta;
-                    phys.vel *= decel;
-
-                    dx = phys.vel.x;
-                    dy = phys.vel.y;
-                    dz = phys.vel.z;
-
-                    let on_ground = (pos.y + phys.vel.y * delta) > 0.001 && dy > -0.1;
-                    phys.grounded = on_ground;
-
-                    if on_ground {
-                        set_to_ground = true;
-                    } else if phys.grounded {
-                        dy = 0.0;
+                let decel = 1.0 / (1.0 + (delta * phys.friction));
+                phys.vel += phys.accel * delta;
+                phys.vel.x *= decel;
+                phys.vel.z *= decel;
+
+                pos.pos.x += phys.vel.x * delta;
+                pos.pos.z += phys.vel.z * delta;
+
+                if let Some(&rest) = rests.get(&entity) {
+                    phys.vel.y -= gravity * delta;
+                    pos.pos.y += phys.vel.y * delta;
+
+                    // Landed, or still standing. Walking off a ledge leaves the rest height
+                    // below, so the body starts falling on the next tick.
+                    phys.grounded = pos.pos.y <= rest && phys.vel.y <= 0.0;
+                    if phys.grounded {
+                        pos.pos.y = rest;
+                        phys.vel.y = 0.0;
                     }
                 }
-
-                let mut pos = world.ecs_mut().positions.get_mut_or_err(entity);
-                pos.pos.x += dx * delta;
-                if set_to_ground {
-                    pos.pos.y = 0.0;
-                } else {
-                    pos.pos.y += dy * delta;
-                }
-                pos.pos.z += dz * delta;
             },
             PhysicsKind::Bullet => {
-                {
-                    let pos = world.ecs().positions.get_or_err(entity).pos;
-                    let mut phys = world.ecs_mut().physics.get_mut_or_err(entity);
-                    dx = phys.vel.x;
-                    dy = phys.vel.y;
-                    dz = phys.vel.z;
-                }
-
-                let mut pos = world.ecs_mut().positions.get_mut_or_err(entity);
-                pos.pos.x += dx * delta;
-                pos.pos.y += dy * delta;
-                pos.pos.z += dz * delta;
+                pos.pos.x += phys.vel.x * delta;
+                pos.pos.y += phys.vel.y * delta;
+                pos.pos.z += phys.vel.z * delta;
             }
         }
 
-        let (w, h) = world.size();
-        let mut pos = world.ecs_mut().positions.get_mut_or_err(entity);
         pos.pos.x = util::clamp(pos.pos.x, 0.0, w as f32);
         pos.pos.z = util::clamp(pos.pos.z, 0.0, h as f32);
-    }
+    });
 }
 
-fn step_bullet(world: &mut World, delta: f32) {
-    let mut bullets = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().bullets.has(*entity) {
-            bullets.push(*entity);
-        }
-    }
+/// Whether gravity pulls on the body.
+fn falls(phys: &Physics) -> bool {
+    phys.kind == PhysicsKind::Physical
+}
 
-    for bullet in bullets {
-        let mut remove = false;
-        {
-            let mut bullet_compo = world.ecs_mut().bullets.get_mut_or_err(bullet);
-            bullet_compo.time_left -= delta;
-            if bullet_compo.time_left < 0.0 {
-                remove = true;
-            }
+fn step_bullet(world: &mut World, delta: f32) {
+    // Shots aimed low end up in the ground.
+    let mut expired: Vec<Entity> = query::join2(&world.ecs().bullets, &world.ecs().positions)
+        .filter(|&(_, _, pos)| pos.pos.y < world::FLOOR_HEIGHT)
+        .map(|(bullet, _, _)| bullet)
+        .collect();
+
+    query::each_mut(&mut world.ecs_mut().bullets, |bullet, bullet_compo| {
+        bullet_compo.time_left -= delta;
+        if bullet_compo.time_left < 0.0 && !expired.contains(&bullet) {
+            expired.push(bullet);
         }
+    });
 
-        if remove {
-            world.push_event(Event::Destroy, bullet);
-        }
+    for bullet in expired {
+        world.push_event(Event::Destroy, bullet);
     }
 }
 
 fn step_holds(world: &mut World) {
-    let mut holds = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().holds.has(*entity) {
-            holds.push(*entity);
-        }
-    }
-
-    for hold in holds {
-        let mut remove = HashSet::new();
-        let mut adj = HashSet::new();
-        let (pos, dir) = {
-            let pos = world.ecs().positions.get_or_err(hold);
-            (pos.pos, pos.dir)
-        };
-
-        {
-            let holding = world.ecs().holds.get_or_err(hold);
-            for entity in holding.0.keys() {
-                if !world.contains(*entity) {
-                    remove.insert(*entity);
-                } else {
-                    let is_holding = holding.0.get(entity).unwrap();
-                    if !is_holding {
-                        adj.insert(*entity);
-                    }
-                }
+    let mut remove = Vec::new();
+    for (hold, holding) in query::iter(&world.ecs().holds) {
+        for entity in holding.0.keys() {
+            if !world.contains(*entity) {
+                remove.push((hold, *entity));
             }
         }
+    }
 
-        for entity in remove {
-            let mut holding = world.ecs_mut().holds.get_mut_or_err(hold);
-            holding.0.remove(&entity);
-        }
-
-        for entity in adj {
-            if let Some(to_set) = world.ecs_mut().positions.get_mut(hold) {
-                to_set.pos = pos;
-                to_set.dir = dir;
-            }
-        }
+    for (hold, entity) in remove {
+        world.ecs_mut().holds.map_mut(|h| h.0.remove(&entity), hold);
     }
 }
 
 fn step_bomb(world: &mut World, delta: f32) {
-    let mut bombs = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().bombs.has(*entity) {
-            bombs.push(*entity);
-        }
-    }
+    let exploded: Vec<(Entity, Point, Option<Entity>, Splash)> =
+        query::join2(&world.ecs().bombs, &world.ecs().positions)
+            .filter(|&(_, bomb, _)| bomb.time_left < 0.0)
+            .map(|(ent, bomb, pos)| (ent, pos.pos, bomb.owner, bomb.blast))
+            .collect();
 
-    for bomb_ent in bombs {
-        let exploded = world.ecs().bombs.get_or_err(bomb_ent).time_left < 0.0;
-        if exploded {
-            let pos = world.position(bomb_ent).unwrap().pos;
-            explod(world, pos);
-            world.push_event(Event::Destroy, bomb_ent)
-        } else {
-            let mut bomb = world.ecs_mut().bombs.get_mut_or_err(bomb_ent);
-            bomb.time_left -= delta;
-        }
+    query::each_mut(&mut world.ecs_mut().bombs, |_, bomb| bomb.time_left -= delta);
+
+    for (bomb_ent, pos, owner, blast) in exploded {
+        explod(world, pos, owner);
+        world.splash_damage(pos, blast, owner);
+        world.push_event(Event::Explosion { pos: pos, radius: blast.radius }, bomb_ent);
+        world.push_event(Event::Destroy, bomb_ent)
     }
 }
 
@@ -341,7 +435,9 @@ use nalgebra::{self, Isometry3, Point3, Translation3, Vector3, Matrix3x1};
 use ncollide::query::Ray3;
 use world::CollisionDataExtra;
 
-fn explod(world: &mut World, point: Point) {
+/// Pushes away everything around `point` that the bomb's owner is allowed to hurt, lighter things
+/// further.
+fn explod(world: &mut World, point: Point, owner: Option<Entity>) {
     let num_rays = 32;
     let groups = CollisionGroups::new();
     let mut impulses = Vec::new();
@@ -354,7 +450,8 @@ fn explod(world: &mut World, point: Point) {
         let ray = Ray3::new(point, dir);
         for (obj, colray) in world.collision_world.interferences_with_ray(&ray, &groups) {
             if let CollisionDataExtra::Entity(entity) = *obj.data() {
-                if world.ecs().physics.has(entity) {
+                let spared = owner.map_or(false, |o| !world.can_damage(o, entity));
+                if world.ecs().physics.has(entity) && !spared {
                     let contact = point + dir * colray.toi;
                     if let Some(impulse) = blast_impulse(&point, contact, force / num_rays as f32) {
                         impulses.push((entity, impulse));
@@ -365,8 +462,7 @@ fn explod(world: &mut World, point: Point) {
     }
 
     for (entity, impulse) in impulses {
-        let mut phys = world.ecs_mut().physics.get_mut_or_err(entity);
-        phys.vel += impulse;
+        world.ecs_mut().physics.get_mut_or_err(entity).apply_impulse(impulse);
     }
 }
 
@@ -384,83 +480,97 @@ fn blast_impulse(blast: &Point, center: Point, power: f32) -> Option<Vector3<f32
     Some(Vector3::from(dir * magni))
 }
 
-fn step_gun(world: &mut World) {
-    let mut guns = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().guns.has(*entity) {
-            guns.push(*entity);
-        }
-    }
-
-    for gun in guns {
-        let (holder_pos, holder_dir) = {
-            let hold = world.ecs().holds.get(gun).unwrap();
-            if hold.0.is_empty() {
-                continue;
-            }
-
-            assert!(hold.0.keys().len() == 1);
-            let owner = hold.0.keys().next().unwrap();
+fn step_gun(world: &mut World, delta: f32) {
+    let held: Vec<(Entity, Point, f32)> = {
+        let ecs = world.ecs();
+        query::join2(&ecs.guns, &ecs.holds)
+            .filter_map(|(gun, _, hold)| {
+                assert!(hold.0.keys().len() <= 1);
+                hold.0.keys()
+                      .next()
+                      .and_then(|owner| ecs.positions.get(*owner))
+                      .map(|pos| (gun, pos.pos, pos.dir))
+            })
+            .collect()
+    };
 
-            let pos = world.ecs().positions.get_or_err(*owner);
-            (pos.pos, pos.dir)
-        };
-        {
-            let mut gun = world.ecs_mut().guns.get_mut_or_err(gun);
+    let ecs = world.ecs_mut();
+    for (gun_ent, holder_pos, holder_dir) in held {
+        if let Some(gun) = ecs.guns.get_mut(gun_ent) {
+            gun.step_reload(delta);
             if !gun.shooting {
                 gun.reset_refire();
             }
             gun.shooting = false;
         }
-        let mut gun_pos = world.ecs_mut().positions.get_mut_or_err(gun);
-        gun_pos.dir = holder_dir;
-        let p2: &mut Point = &mut gun_pos.pos;
-        let p: Point = Point::new(holder_pos.x, holder_pos.y, holder_pos.z);
-        p2.x = p.x;
-        p2.y = p.y;
-        p2.z = p.z;
+        if let Some(gun_pos) = ecs.positions.get_mut(gun_ent) {
+            gun_pos.pos = holder_pos;
+            gun_pos.dir = holder_dir;
+        }
     }
 }
 
+fn step_melee(world: &mut World, delta: f32) {
+    query::each_mut(&mut world.ecs_mut().melees, |_, melee| melee.step(delta));
+}
+
 fn step_healths(world: &mut World) {
-    let mut healths = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().healths.has(*entity) {
-            healths.push(*entity);
-        }
+    let dead: Vec<Entity> = query::iter(&world.ecs().healths)
+        .filter(|&(_, health)| health.is_dead())
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in dead {
+        world.push_event(Event::Killed { by: None }, entity);
     }
+}
 
-    for health in healths {
-        if world.ecs().healths.get_or_err(health).is_dead() {
-            world.push_event(Event::Destroy, health);
-        }
+fn step_areas(world: &mut World) {
+    let tile = |p: Point| (p.x.floor() as i32, p.z.floor() as i32);
+    let moved: Vec<(Entity, (i32, i32), (i32, i32))> =
+        query::join2(&world.ecs().charas, &world.ecs().positions)
+            .map(|(entity, _, pos)| (entity, tile(pos.last_pos), tile(pos.pos)))
+            .filter(|&(_, from, to)| from != to)
+            .collect();
+
+    for (entity, from, to) in moved {
+        world.push_event(Event::EnteredArea { from: from, to: to }, entity);
     }
 }
 
 fn step_ai(world: &mut World, recheck: bool, delta: f32) {
-    let mut ais = Vec::new();
-    for entity in world.entities() {
-        if world.ecs().ais.has(*entity) {
-            ais.push(*entity);
-        }
+    // Lua sensors and actions draw from the AI's own stream, so an AI rolling dice doesn't change
+    // what the hooks roll.
+    let seed = world.rng(RngStream::Ai).gen::<[u32; 4]>();
+    lua::world::seed_rng(seed);
+
+    if recheck {
+        ai::recheck_all(world);
     }
 
+    let ais: Vec<Entity> = query::iter(&world.ecs().ais).map(|(entity, _)| entity).collect();
+
     for entity in ais {
         stop_moving(world, entity);
-        let action = ai::run(entity, world, recheck);
+        let action = ai::run(entity, world);
         match action {
             Some(Action::Go(dir)) => move_in_dir(world, entity, dir),
             Some(Action::Shoot(dir)) => {
                 face_dir(world, entity, dir);
                 shoot(world, entity, delta);
             }
+            Some(Action::Reload) => reload(world, entity),
+            Some(Action::Melee(dir)) => {
+                face_dir(world, entity, dir);
+                swing(world, entity);
+            }
             _ => stop_moving(world, entity),
         }
     }
 }
 
-fn update_camera(context: &mut GameContext) {
-    let camera_entity = context.state.world.camera;
+fn update_camera(world: &mut World) {
+    let camera_entity = world.camera;
 
     if camera_entity.is_none() {
         return;
@@ -468,9 +578,9 @@ fn update_camera(context: &mut GameContext) {
 
     let camera_entity = camera_entity.unwrap();
 
-    let following_pos = context.state.world.camera_pos();
+    let following_pos = world.camera_pos();
 
-    let mut pos = context.state.world.ecs_mut().positions.get_mut_or_err(camera_entity);
+    let mut pos = world.ecs_mut().positions.get_mut_or_err(camera_entity);
     if let Some(p) = following_pos {
         let p2: &mut Point = &mut pos.pos;
         let p: Point = Point::new(p.x, p.y, p.z);
@@ -481,11 +591,26 @@ fn update_camera(context: &mut GameContext) {
 }
 
 fn restart_game(context: &mut GameContext) {
-    *context = GameContext::new();
+    // Derive the next seed from the current world, so a whole session is reproducible from the
+    // first seed.
+    let seed = context.state.world.rng(RngStream::Session).gen::<u64>();
+    *context = if context.headless {
+        GameContext::new_headless(seed)
+    } else {
+        GameContext::new(seed)
+    };
+}
+
+fn screen_size(context: &GameContext) -> (u32, u32) {
+    if context.headless {
+        (renderer::SCREEN_WIDTH, renderer::SCREEN_HEIGHT)
+    } else {
+        renderer::with(|rc| rc.viewport.scaled_size())
+    }
 }
 
 fn update_look(context: &mut GameContext, mouse: &MouseState) {
-    let size = renderer::with(|rc| rc.viewport.scaled_size());
+    let size = screen_size(context);
     let center = ((size.0 / 2) as i32, (size.1 / 2) as i32);
     let mouse = (mouse.pos.0.max(0), mouse.pos.1.max(0));
 
@@ -502,16 +627,52 @@ fn run_command(context: &mut GameContext, command: Command, delta: f32) {
         Command::Move(dir) => move_in_dir(&mut context.state.world, player, dir),
         Command::Jump => jump(&mut context.state.world, player),
         Command::Shoot => shoot(&mut context.state.world, player, delta),
+        Command::Reload => reload(&mut context.state.world, player),
+        Command::Melee => swing(&mut context.state.world, player),
+        Command::PickUp => {
+            context.state.world.pick_up(player);
+        },
+        Command::DropWeapon => {
+            context.state.world.drop_weapon(player);
+        },
+        Command::CycleWeapon(step) => context.state.world.cycle_weapon(player, step),
         Command::Wait => stop_moving(&mut context.state.world, player),
         Command::Bom => bom(&mut context.state.world, player),
 
         Command::RotateCamera(rot) => rotate_camera(&mut context.state.world, rot),
-        Command::ReloadShaders => renderer::with_mut(|rc| rc.reload_shaders()),
+        Command::ReloadShaders => if !context.headless {
+            renderer::with_mut(|rc| rc.reload_shaders())
+        },
         Command::Restart => restart_game(context),
+        Command::QuickSave => quick_save(context),
+        Command::QuickLoad => quick_load(context),
         Command::Quit => (),
     }
 }
 
+fn quick_save(context: &mut GameContext) {
+    match context.state.world.save(QUICK_SAVE_PATH) {
+        Ok(()) => {
+            log!("Saved to {}.", QUICK_SAVE_PATH);
+        },
+        Err(e) => {
+            log!("Failed to save to {}: {:?}", QUICK_SAVE_PATH, e);
+        },
+    }
+}
+
+fn quick_load(context: &mut GameContext) {
+    match World::load(QUICK_SAVE_PATH) {
+        Ok(world) => {
+            context.state.world = world;
+            log!("Loaded {}.", QUICK_SAVE_PATH);
+        },
+        Err(e) => {
+            log!("Failed to load {}: {:?}", QUICK_SAVE_PATH, e);
+        },
+    }
+}
+
 fn rotate_camera(world: &mut World, rot: f32) {
     world.camera.map(|c| {
         let mut cam = world.ecs_mut().cameras.get_mut_or_err(c);
@@ -540,12 +701,12 @@ fn move_in_dir(world: &mut World, entity: Entity, dir: Direction) {
 }
 
 fn jump(world: &mut World, entity: Entity) {
-    //let mut phys = world.ecs_mut().physics.get_mut_or_err(entity);
+    let mut phys = world.ecs_mut().physics.get_mut_or_err(entity);
 
-    //if phys.grounded {
-    //    let jump = debug::get("jump");
-    //    phys.dy = jump;
-    //}
+    if phys.grounded {
+        phys.vel.y = debug::get("jump");
+        phys.grounded = false;
+    }
 }
 
 fn stop_moving(world: &mut World, entity: Entity) {
@@ -563,45 +724,150 @@ fn bom(world: &mut World, entity: Entity) {
         point::relative(pos.pos, Point::new(1.5, 0.0, 0.0), pos.dir)
     };
 
-    world.spawn(prefab::bomb(), pos);
+    if let Some(bomb) = world.spawn_prefab("bomb", pos) {
+        if let Some(b) = world.ecs_mut().bombs.get_mut(bomb) {
+            b.owner = Some(entity);
+        }
+    }
 }
 
-fn shoot(world: &mut World, firing: Entity, delta: f32) {
-    let gun = {
-        let holds = world.ecs().holds.get_or_err(firing);
-        let mut gun = None;
-        for entity in holds.0.keys() {
-            if let Some(g) = world.ecs().guns.get(*entity) {
-                gun = Some(*entity);
-                break;
+fn reload(world: &mut World, entity: Entity) {
+    if let Some(gun_ent) = world.held_gun(entity) {
+        world.ecs_mut().guns.get_mut_or_err(gun_ent).start_reload();
+    }
+}
+
+/// Hits every chara in an arc in front of `attacker`, found by casting rays across the arc.
+fn swing(world: &mut World, attacker: Entity) {
+    let melee = match world.ecs_mut().melees.get_mut(attacker) {
+        Some(melee) => if melee.swing() {
+            melee.clone()
+        } else {
+            return;
+        },
+        None => return,
+    };
+
+    let (origin, facing) = {
+        let pos = world.ecs().positions.get_or_err(attacker);
+        (pos.pos, pos.dir)
+    };
+
+    let num_rays = 9;
+    let groups = CollisionGroups::new();
+    let mut hit: Vec<(Entity, Vector3<f32>)> = Vec::new();
+    for i in 0..num_rays {
+        let angle = facing - melee.arc / 2.0 + melee.arc * i as f32 / (num_rays - 1) as f32;
+        let dir = Vector3::new(angle.cos(), 0.0, angle.sin());
+        let ray = Ray3::new(origin, dir * melee.range);
+        for (obj, colray) in world.collision_world.interferences_with_ray(&ray, &groups) {
+            if let CollisionDataExtra::Entity(entity) = *obj.data() {
+                let in_reach = colray.toi <= 1.0;
+                let already_hit = hit.iter().any(|&(e, _)| e == entity);
+                if in_reach && !already_hit && entity != attacker && world.ecs().charas.has(entity) &&
+                    world.can_damage(attacker, entity) {
+                    hit.push((entity, dir));
+                }
             }
         }
-        gun
-    };
+    }
+
+    for (entity, dir) in hit {
+        let event = Event::Damaged {
+            amount: melee.damage,
+            source: Some(attacker),
+            kind: DamageKind::Melee,
+            zone: HitZone::Body,
+        };
+        world.push_event(event, entity);
+        if let Some(phys) = world.ecs_mut().physics.get_mut(entity) {
+            phys.apply_impulse(dir * melee.knockback);
+        }
+    }
+}
 
-    if let Some(gun_ent) = gun {
+fn shoot(world: &mut World, firing: Entity, delta: f32) {
+    if let Some(gun_ent) = world.held_gun(firing) {
         let bullet_count = {
             let mut gun = world.ecs_mut().guns.get_mut_or_err(gun_ent);
             gun.shoot(delta)
         };
 
+        if bullet_count > 0 {
+            world.push_event(Event::WeaponFired { weapon: gun_ent }, firing);
+        }
+
         let pos = {
             let pos = world.ecs().positions.get_or_err(firing);
             let dir = pos.dir;
             point::relative(pos.pos, Point::new(1.5, 0.0, 0.0), pos.dir)
         };
 
-        let spread = world.ecs().guns.get_or_err(gun_ent).spread;
-        for count in 0..bullet_count {
-            let dir = world.ecs().positions.get_or_err(firing).dir + rand::thread_rng().gen_range(-spread, spread);
-            if let Some(bullet) = world.spawn(prefab::bullet(firing), pos) {
+        let (spread, kind) = {
+            let gun = world.ecs().guns.get_or_err(gun_ent);
+            (gun.spread, gun.bullet.clone())
+        };
+        let def = match ballistics::def(&kind) {
+            Some(def) => def,
+            None => {
+                log!("Unknown bullet kind {}", kind);
+                return;
+            },
+        };
+
+        for _ in 0..bullet_count * def.pellets {
+            let offset = world.rng(RngStream::Combat).gen_range(-spread, spread);
+            // Spread goes up and down too, so shots from further away can hit the head.
+            let climb = world.rng(RngStream::Combat).gen_range(-spread, spread);
+            let dir = world.ecs().positions.get_or_err(firing).dir + offset;
+            if let Some(bullet) = world.spawn(prefab::bullet(&def, firing), pos) {
                 let mut phys = world.ecs_mut().physics.get_mut_or_err(bullet);
 
-                let speed = debug::get("bullet_speed");
-                let dx = dir.cos() * speed;
-                let dz = dir.sin() * speed;
-                phys.vel += Vector::new(dx, 0.0, dz);
+                let dx = dir.cos() * def.speed;
+                let dy = climb.sin() * def.speed;
+                let dz = dir.sin() * def.speed;
+                phys.vel += Vector::new(dx, dy, dz);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systems_ordered() {
+        assert_eq!(make_scheduler().conflicts(), Vec::new());
+    }
+
+    #[test]
+    fn test_jump_and_land() {
+        let mut world = World::new(32, 32, 1);
+        let player = world.player().unwrap();
+        step_physics(&mut world, 0.1);
+        assert!(world.ecs().physics.get_or_err(player).grounded);
+
+        jump(&mut world, player);
+        step_physics(&mut world, 0.1);
+        assert!(world.position(player).unwrap().pos.y > 0.0);
+        assert!(!world.ecs().physics.get_or_err(player).grounded);
+
+        for _ in 0..20 {
+            step_physics(&mut world, 0.1);
+        }
+        assert_eq!(world.position(player).unwrap().pos.y, 0.0);
+        assert!(world.ecs().physics.get_or_err(player).grounded);
+    }
+
+    #[test]
+    fn test_stand_on_blocks() {
+        let mut world = World::new(32, 32, 1);
+        let player = world.player().unwrap();
+        world.spawn_prefab("cone", Point::new(16.0, 0.0, 16.0)).unwrap();
+        world.ecs_mut().positions.get_mut_or_err(player).pos = Point::new(16.0, 2.0, 16.0);
+        world.update_physics(false);
+
+        assert!(world.resting_height(player) > 0.0);
+    }
+}
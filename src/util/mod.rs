@@ -13,6 +13,18 @@ pub fn read_string(path: &str) -> String {
     s
 }
 
+/// Like `read_string`, but returns an error instead of panicking.
+pub fn read_string_checked(path: &str) -> Result<String, String> {
+    use std::io::Read;
+    use std::fs::File;
+
+    let mut s = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut s))
+        .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    Ok(s)
+}
+
 pub fn get_duration_millis(duration: &Duration) -> u64 {
     let nanos = duration.subsec_nanos() as u64;
     (1000 * 1000 * 1000 * duration.as_secs() + nanos) / (1000 * 1000)
@@ -25,6 +25,16 @@ pub fn toml_value_from_file(filename: &str) -> Value {
     toml_value_from_string(&toml_str)
 }
 
+/// Like `toml_value_from_file`, but returns read and parse errors instead of panicking, for data
+/// that is reloaded while the game is running.
+pub fn try_toml_value_from_file(filename: &str) -> Result<Value, String> {
+    let mut data = String::new();
+    File::open(filename)
+        .and_then(|mut file| file.read_to_string(&mut data))
+        .map_err(|e| format!("Cannot read {}: {}", filename, e))?;
+    data.parse::<Value>().map_err(|e| format!("Invalid TOML in {}: {}", filename, e))
+}
+
 pub fn get_value_in_table<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
     match *value {
         Value::Table(ref table) => {
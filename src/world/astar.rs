@@ -71,7 +71,7 @@ impl Grid {
 
         let mut groups = CollisionGroups::new();
         groups.set_membership(&[4]);
-        groups.set_whitelist(&[1, 2, 3]);
+        groups.set_whitelist(&[1, 2, 3, 5]);
         groups.set_blacklist(&[4]);
 
         Grid {
@@ -84,17 +84,36 @@ impl Grid {
     pub fn discretize(&mut self, world: &CollideWorld) {
         for x in 0..self.size.0 {
             for z in 0..self.size.1 {
-                let pos = Point2d::new(x as i32, z as i32);
-                let mins = Point::new((x) as f32 + 0.2, -10.0, (z) as f32 + 0.2);
-                let maxs = Point::new((x) as f32 + 0.8,  10.0, (z) as f32 + 0.8);
-                let aabb = AABB::new(mins, maxs);
-                let mut i = world.interferences_with_aabb(&aabb, &self.groups);
-                let blocked = i.next().is_some();
-                *self.nodes.get_mut(&pos).unwrap() = blocked;
+                self.discretize_cell(world, x, z);
             }
         }
     }
 
+    /// Rechecks only the cells within `radius` of `center`, for when something blocking is
+    /// removed and waiting for the next full `discretize` would leave paths going around it.
+    pub fn discretize_around(&mut self, world: &CollideWorld, center: Point, radius: f32) {
+        let clamp = |v: f32, size: u32| (v.max(0.0) as u32).min(size);
+        let (min_x, max_x) = (clamp((center.x - radius).floor(), self.size.0),
+                              clamp((center.x + radius).ceil(), self.size.0));
+        let (min_z, max_z) = (clamp((center.z - radius).floor(), self.size.1),
+                              clamp((center.z + radius).ceil(), self.size.1));
+
+        for x in min_x..max_x {
+            for z in min_z..max_z {
+                self.discretize_cell(world, x, z);
+            }
+        }
+    }
+
+    fn discretize_cell(&mut self, world: &CollideWorld, x: u32, z: u32) {
+        let pos = Point2d::new(x as i32, z as i32);
+        let mins = Point::new((x) as f32 + 0.2, -10.0, (z) as f32 + 0.2);
+        let maxs = Point::new((x) as f32 + 0.8,  10.0, (z) as f32 + 0.8);
+        let aabb = AABB::new(mins, maxs);
+        let blocked = world.interferences_with_aabb(&aabb, &self.groups).next().is_some();
+        *self.nodes.get_mut(&pos).unwrap() = blocked;
+    }
+
     fn neighbors(&self, center: Point2d) -> Vec<Point2d> {
         if !self.nodes.contains_key(&center) {
             return Vec::new();
@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+
+use calx_ecs::Entity;
+use nalgebra::{Matrix3x1, Vector3};
+
+use debug;
+use ecs::components::{DamageKind, HitZone};
+use point::Point;
+use super::World;
+
+/// Something that happened to an entity during a tick.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// Was hit for `amount` damage, before armor. `source` is whatever dealt the damage, like the
+    /// shooter of a bullet.
+    Damaged { amount: i32, source: Option<Entity>, kind: DamageKind, zone: HitZone },
+
+    /// Ran out of health. `by` is whatever dealt the final blow.
+    Killed { by: Option<Entity> },
+
+    /// Should be removed from the world at the end of the tick.
+    Destroy,
+
+    /// Was pushed by a collision.
+    Collide(Matrix3x1<f32>),
+
+    /// Fired a shot from the held `weapon`.
+    WeaponFired { weapon: Entity },
+
+    /// Blew up.
+    Explosion { pos: Point, radius: f32 },
+
+    /// Picked up `item`.
+    PickedUp { item: Entity },
+
+    /// Moved into a new tile.
+    EnteredArea { from: (i32, i32), to: (i32, i32) },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Damaged,
+    Killed,
+    Destroy,
+    Collide,
+    WeaponFired,
+    Explosion,
+    PickedUp,
+    EnteredArea,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match *self {
+            Event::Damaged { .. } => EventKind::Damaged,
+            Event::Killed { .. } => EventKind::Killed,
+            Event::Destroy => EventKind::Destroy,
+            Event::Collide(_) => EventKind::Collide,
+            Event::WeaponFired { .. } => EventKind::WeaponFired,
+            Event::Explosion { .. } => EventKind::Explosion,
+            Event::PickedUp { .. } => EventKind::PickedUp,
+            Event::EnteredArea { .. } => EventKind::EnteredArea,
+        }
+    }
+}
+
+/// Called with each event of the kind it subscribed to. Subscribers that need to keep state
+/// between calls can keep it in a global.
+pub type Subscriber = fn(&mut World, &Event, Entity);
+
+/// Queues events in the order they were pushed and hands them out to subscribers.
+pub struct EventBus {
+    queue: VecDeque<(Event, Entity)>,
+    subscribers: Vec<(Option<EventKind>, Subscriber)>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            queue: VecDeque::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: Event, entity: Entity) {
+        self.queue.push_back((event, entity));
+    }
+
+    pub fn pop(&mut self) -> Option<(Event, Entity)> {
+        self.queue.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Subscribes to one kind of event.
+    pub fn subscribe(&mut self, kind: EventKind, subscriber: Subscriber) {
+        self.subscribers.push((Some(kind), subscriber));
+    }
+
+    /// Subscribes to every event.
+    pub fn subscribe_all(&mut self, subscriber: Subscriber) {
+        self.subscribers.push((None, subscriber));
+    }
+
+    /// The subscribers for an event kind, in the order they subscribed.
+    pub fn subscribers(&self, kind: EventKind) -> Vec<Subscriber> {
+        self.subscribers.iter()
+            .filter(|&&(k, _)| k.map_or(true, |k| k == kind))
+            .map(|&(_, s)| s)
+            .collect()
+    }
+}
+
+impl World {
+    pub fn push_event(&mut self, event: Event, entity: Entity) {
+        self.events.push(event, entity);
+    }
+
+    pub fn subscribe(&mut self, kind: EventKind, subscriber: Subscriber) {
+        self.events.subscribe(kind, subscriber);
+    }
+
+    pub fn subscribe_all(&mut self, subscriber: Subscriber) {
+        self.events.subscribe_all(subscriber);
+    }
+
+    /// Sends every queued event to its subscribers, oldest first. Events pushed by subscribers
+    /// are handled in the same call.
+    pub fn handle_events(&mut self) {
+        while let Some((event, entity)) = self.events.pop() {
+            for subscriber in self.events.subscribers(event.kind()) {
+                subscriber(self, &event, entity);
+            }
+        }
+    }
+
+    /// The world's own reactions to events, which every world subscribes to first.
+    pub(super) fn subscribe_defaults(&mut self) {
+        self.subscribe(EventKind::Damaged, on_damaged);
+        self.subscribe(EventKind::Killed, on_destroy);
+        self.subscribe(EventKind::Killed, log_kill);
+        self.subscribe(EventKind::Destroy, on_destroy);
+        self.subscribe(EventKind::Collide, on_collide);
+    }
+}
+
+fn on_damaged(world: &mut World, event: &Event, entity: Entity) {
+    if let Event::Damaged { amount, source, kind, zone } = *event {
+        if debug::god_mode() && world.player() == Some(entity) {
+            return;
+        }
+
+        let amount = damage_taken(world, entity, amount, kind, zone);
+
+        let killed = match world.ecs_mut().healths.get_mut(entity) {
+            Some(health) => {
+                let was_dead = health.is_dead();
+                health.hurt(amount);
+                !was_dead && health.is_dead()
+            },
+            None => false,
+        };
+
+        if killed {
+            world.push_event(Event::Killed { by: source }, entity);
+        }
+    }
+}
+
+/// How much of a hit gets through: head hits do extra damage, and armor stops its share of the
+/// rest.
+pub fn damage_taken(world: &World, entity: Entity, amount: i32, kind: DamageKind, zone: HitZone)
+                    -> i32 {
+    let multiplier = match zone {
+        HitZone::Head => debug::get("headshot_multiplier"),
+        HitZone::Body => 1.0,
+    };
+    let resistance = world.ecs().armors.get(entity).map_or(0.0, |a| a.resistance(kind, zone));
+    (amount as f32 * multiplier * (1.0 - resistance)).round() as i32
+}
+
+fn on_destroy(world: &mut World, _event: &Event, entity: Entity) {
+    world.kill_list.push(entity);
+}
+
+fn log_kill(world: &mut World, event: &Event, entity: Entity) {
+    if let Event::Killed { by: Some(killer) } = *event {
+        let name = |e: Entity| {
+            world.ecs().names.get(e).map_or("Something".to_string(), |n| n.name.clone())
+        };
+        log!("{} was killed by {}.", name(entity), name(killer));
+    }
+}
+
+fn on_collide(world: &mut World, event: &Event, entity: Entity) {
+    if let Event::Collide(vec) = *event {
+        if let Some(phys) = world.ecs_mut().physics.get_mut(entity) {
+            phys.apply_impulse(Vector3::new(-vec.x, 0.0, -vec.z));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::Ecs;
+    use ecs::ballistics::Splash;
+    use point::Point2d;
+
+    fn nothing(_world: &mut World, _event: &Event, _entity: Entity) {}
+
+    #[test]
+    fn test_fifo() {
+        let mut ecs = Ecs::new();
+        let a = ecs.make();
+        let b = ecs.make();
+
+        let mut bus = EventBus::new();
+        bus.push(Event::Destroy, a);
+        bus.push(Event::Killed { by: None }, b);
+        assert_eq!(bus.pop().map(|(e, _)| e.kind()), Some(EventKind::Destroy));
+        assert_eq!(bus.pop().map(|(e, _)| e.kind()), Some(EventKind::Killed));
+        assert!(bus.is_empty());
+    }
+
+    #[test]
+    fn test_subscribers() {
+        let mut bus = EventBus::new();
+        bus.subscribe(EventKind::Damaged, nothing);
+        bus.subscribe_all(nothing);
+        assert_eq!(bus.subscribers(EventKind::Damaged).len(), 2);
+        assert_eq!(bus.subscribers(EventKind::Destroy).len(), 1);
+    }
+
+    #[test]
+    fn test_armor_and_head_hits() {
+        let mut world = World::new(32, 32, 1);
+        let player = world.player().unwrap();
+        let trooper = world.spawn_prefab("trooper", Point::new(16.0, 0.0, 16.0)).unwrap();
+        let multiplier = debug::get("headshot_multiplier");
+
+        let taken = |e, kind, zone| damage_taken(&world, e, 100, kind, zone);
+        assert_eq!(taken(player, DamageKind::Ballistic, HitZone::Body), 100);
+        assert_eq!(taken(player, DamageKind::Ballistic, HitZone::Head), (100.0 * multiplier) as i32);
+        assert_eq!(taken(trooper, DamageKind::Ballistic, HitZone::Body), 60);
+        assert_eq!(taken(trooper, DamageKind::Fire, HitZone::Body), 100);
+        assert_eq!(taken(trooper, DamageKind::Ballistic, HitZone::Head), (50.0 * multiplier) as i32);
+    }
+
+    #[test]
+    fn test_damage_kills() {
+        let mut world = World::new(32, 32, 1);
+        let player = world.player().unwrap();
+        let event = Event::Damaged {
+            amount: 100000,
+            source: None,
+            kind: DamageKind::Ballistic,
+            zone: HitZone::Body,
+        };
+        world.push_event(event, player);
+        world.handle_events();
+        world.purge_dead();
+        assert!(!world.contains(player));
+    }
+
+    #[test]
+    fn test_explosion_destroys_walls() {
+        let mut world = World::new(32, 32, 1);
+        let pos = Point::new(16.5, 0.0, 16.5);
+        let wall = world.spawn_prefab("wall", pos).unwrap();
+        world.update_physics(true);
+        assert_eq!(world.grid.nodes.get(&Point2d::new(16, 16)), Some(&true));
+
+        world.splash_damage(pos, Splash { radius: 4.0, damage: 1000 }, None);
+        world.handle_events();
+        world.purge_dead();
+        assert!(!world.contains(wall));
+        assert_eq!(world.grid.nodes.get(&Point2d::new(16, 16)), Some(&false));
+    }
+}
@@ -0,0 +1,114 @@
+//! Which teams are out to get each other, read from `data/factions.toml`.
+
+use std::collections::{HashMap, HashSet};
+
+use util;
+
+pub const FACTIONS_PATH: &str = "data/factions.toml";
+
+pub struct Factions {
+    /// Whether members of the same faction can hurt each other.
+    pub friendly_fire: bool,
+    hostile: HashMap<String, HashSet<String>>,
+}
+
+#[derive(Deserialize)]
+struct FactionsDef {
+    #[serde(default)]
+    friendly_fire: bool,
+    #[serde(default)]
+    factions: HashMap<String, FactionDef>,
+}
+
+#[derive(Deserialize)]
+struct FactionDef {
+    #[serde(default)]
+    hostile: Vec<String>,
+}
+
+impl Factions {
+    pub fn new() -> Self {
+        Factions {
+            friendly_fire: false,
+            hostile: HashMap::new(),
+        }
+    }
+
+    pub fn from_toml(filename: &str) -> Result<Self, String> {
+        let def: FactionsDef = util::toml::try_toml_value_from_file(filename)?
+            .try_into()
+            .map_err(|e| format!("Bad factions in {}: {}", filename, e))?;
+
+        let mut factions = Factions::new();
+        factions.friendly_fire = def.friendly_fire;
+        for name in def.factions.keys() {
+            factions.hostile.entry(name.clone()).or_insert_with(HashSet::new);
+        }
+
+        for (name, faction) in def.factions.iter() {
+            for enemy in faction.hostile.iter() {
+                if !factions.contains(enemy) {
+                    return Err(format!("Faction {} in {} is hostile to unknown faction {}",
+                                       name, filename, enemy));
+                }
+                // Hostility goes both ways, so only one side has to list it.
+                factions.hostile.get_mut(name).unwrap().insert(enemy.clone());
+                factions.hostile.get_mut(enemy).unwrap().insert(name.clone());
+            }
+        }
+
+        Ok(factions)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.hostile.contains_key(name)
+    }
+
+    pub fn are_hostile(&self, a: &str, b: &str) -> bool {
+        a != b && self.hostile.get(a).map_or(false, |enemies| enemies.contains(b))
+    }
+
+    /// Whether something from faction `source` may damage something from faction `target`.
+    pub fn can_damage(&self, source: &str, target: &str) -> bool {
+        source != target || self.friendly_fire
+    }
+}
+
+fn factions_from_toml() -> Factions {
+    match Factions::from_toml(FACTIONS_PATH) {
+        Ok(factions) => factions,
+        Err(e) => {
+            log!("Couldn't load factions: {}", e);
+            Factions::new()
+        },
+    }
+}
+
+make_global!(FACTIONS, Factions, factions_from_toml());
+
+pub use self::instance::*;
+
+/// Rereads the faction file. If it doesn't parse, the error is logged and the old factions are
+/// kept.
+pub fn reload() {
+    match Factions::from_toml(FACTIONS_PATH) {
+        Ok(factions) => with_mut(|f| *f = factions),
+        Err(e) => log!("Couldn't reload factions: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostility() {
+        let factions = Factions::from_toml(FACTIONS_PATH).unwrap();
+        assert!(factions.are_hostile("player", "goons"));
+        assert!(factions.are_hostile("goons", "player"));
+        assert!(!factions.are_hostile("goons", "goons"));
+
+        assert!(factions.can_damage("player", "goons"));
+        assert_eq!(factions.can_damage("goons", "goons"), factions.friendly_fire);
+    }
+}
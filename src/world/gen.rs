@@ -1,7 +1,7 @@
-use ecs::prefab;
 use point::*;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use super::World;
+use super::rng::RngStream;
 
 const BLOCK_SIZE: u32 = 32;
 const ROAD_WIDTH: u32 = 4;
@@ -21,32 +21,41 @@ pub fn city(world: &mut World) {
     for block in blocks(world.size()) {
         paint_road(world, block);
 
-        use rand::{thread_rng, Rng};
-
         let choices = [1, 2, 3];
-        let mut rng = thread_rng();
-        match rng.choose(&choices) {
-            Some(&1) => park(world, block),
-            Some(&2) => house(world, block),
-            Some(&3) => house(world, block),
+        let choice = world.rng(RngStream::Gen).choose(&choices).cloned();
+        match choice {
+            Some(1) => park(world, block),
+            Some(2) => house(world, block),
+            Some(3) => house(world, block),
             _ => unreachable!(),
         }
     }
 }
 
 fn park(world: &mut World, block: (u32, u32)) {
+    let count = world.rng(RngStream::Gen).gen_range(4, 12);
+    for _ in 0..count {
+        let (x, y) = {
+            let rng = world.rng(RngStream::Gen);
+            (rng.gen_range(2, BLOCK_SIZE - ROAD_WIDTH - 2),
+             rng.gen_range(2, BLOCK_SIZE - ROAD_WIDTH - 2))
+        };
+        world.spawn_prefab("cone", Point::new((block.0 + x) as f32, 0.0, (block.1 + y) as f32));
+    }
 }
 
 fn house(world: &mut World, block: (u32, u32)) {
-    let mut rng = thread_rng();
-    let sx = rng.gen_range(2, 4);
-    let sy = rng.gen_range(2, 4);
-    let ex = rng.gen_range(BLOCK_SIZE - 16, BLOCK_SIZE - 8);
-    let ey = rng.gen_range(BLOCK_SIZE - 16, BLOCK_SIZE - 8);
+    let (sx, sy, ex, ey) = {
+        let rng = world.rng(RngStream::Gen);
+        (rng.gen_range(2, 4),
+         rng.gen_range(2, 4),
+         rng.gen_range(BLOCK_SIZE - 16, BLOCK_SIZE - 8),
+         rng.gen_range(BLOCK_SIZE - 16, BLOCK_SIZE - 8))
+    };
     for x in sx..ex {
         for y in sy..ey {
             if x == sx || x == ex-1 || y == sy {
-                world.spawn(prefab::wall(), Point::new((block.0 + x) as f32, 0.0, (block.1 + y) as f32));
+                world.spawn_prefab("wall", Point::new((block.0 + x) as f32, 0.0, (block.1 + y) as f32));
             }
         }
     }
@@ -0,0 +1,194 @@
+//! Carrying things. `Holds` links a holder and what it holds from both sides; charas with an
+//! `Inventory` also keep their weapons in slots and have one of them in hand.
+
+use calx_ecs::Entity;
+use nalgebra;
+
+use debug;
+use ecs::traits::*;
+use super::{Event, World};
+
+impl World {
+    /// Whoever is holding `item`.
+    pub fn holder(&self, item: Entity) -> Option<Entity> {
+        self.ecs.holds.get(item).and_then(|holds| {
+            holds.0.iter().find(|&(_, &is_holder)| !is_holder).map(|(&holder, _)| holder)
+        })
+    }
+
+    /// Everything `entity` is holding.
+    pub fn held_items(&self, entity: Entity) -> Vec<Entity> {
+        self.ecs.holds.get(entity).map_or(Vec::new(), |holds| {
+            holds.0.iter().filter(|&(_, &is_holder)| is_holder).map(|(&item, _)| item).collect()
+        })
+    }
+
+    /// The gun in the entity's hand. Without an inventory, that is the first gun it holds.
+    pub fn held_gun(&self, entity: Entity) -> Option<Entity> {
+        let gun = match self.ecs.inventories.get(entity) {
+            Some(inventory) => inventory.wielded(),
+            None => self.held_items(entity).into_iter().find(|e| self.ecs.guns.has(*e)),
+        };
+
+        match gun {
+            Some(gun) if self.ecs.guns.has(gun) => Some(gun),
+            _ => None,
+        }
+    }
+
+    /// Whether `item` is being carried somewhere out of sight, like a weapon that isn't in hand.
+    pub fn is_stowed(&self, item: Entity) -> bool {
+        self.holder(item)
+            .and_then(|holder| self.ecs.inventories.get(holder))
+            .map_or(false, |inventory| inventory.wielded() != Some(item))
+    }
+
+    pub fn is_item(&self, entity: Entity) -> bool {
+        self.ecs.guns.has(entity)
+    }
+
+    /// Gives `item` to `chara`, taking it from whoever had it before. If the chara's inventory is
+    /// full, the weapon in hand is dropped to make room. Returns false without doing anything if
+    /// `item` isn't an item or either of them can't hold things.
+    pub fn equip(&mut self, chara: Entity, item: Entity) -> bool {
+        if !self.is_item(item) || !self.ecs.holds.has(chara) || !self.ecs.holds.has(item) {
+            return false;
+        }
+
+        if let Some(old) = self.holder(item) {
+            self.unequip(old, item);
+        }
+
+        let full = self.ecs.inventories.get(chara).map_or(false, |i| i.is_full());
+        if full {
+            self.drop_weapon(chara);
+        }
+
+        {
+            let mut holds = self.ecs.holds.get_mut_or_err(chara);
+            holds.0.insert(item, true);
+        }
+        {
+            let mut holds = self.ecs.holds.get_mut_or_err(item);
+            holds.0.insert(chara, false);
+        }
+        if let Some(inventory) = self.ecs.inventories.get_mut(chara) {
+            inventory.add(item);
+        }
+        true
+    }
+
+    /// Takes `item` away from `chara`, leaving it where it is.
+    pub fn unequip(&mut self, chara: Entity, item: Entity) {
+        if let Some(holds) = self.ecs.holds.get_mut(chara) {
+            holds.0.remove(&item);
+        }
+        if let Some(holds) = self.ecs.holds.get_mut(item) {
+            holds.0.remove(&chara);
+        }
+        if let Some(inventory) = self.ecs.inventories.get_mut(chara) {
+            inventory.remove(item);
+        }
+    }
+
+    /// Drops the weapon in hand on the floor.
+    pub fn drop_weapon(&mut self, chara: Entity) -> Option<Entity> {
+        let weapon = self.held_gun(chara);
+        if let Some(weapon) = weapon {
+            self.unequip(chara, weapon);
+        }
+        weapon
+    }
+
+    /// Drops everything `chara` is carrying.
+    pub fn drop_all(&mut self, chara: Entity) {
+        for item in self.held_items(chara) {
+            self.unequip(chara, item);
+        }
+    }
+
+    /// Cuts every `Holds` link to and from `entity`, so nothing refers to it after it is removed.
+    pub(super) fn release_all(&mut self, entity: Entity) {
+        self.drop_all(entity);
+        if let Some(holder) = self.holder(entity) {
+            self.unequip(holder, entity);
+        }
+    }
+
+    /// The closest item within reach of `chara` that nobody is holding.
+    pub fn nearest_item(&self, chara: Entity) -> Option<Entity> {
+        let pos = match self.position(chara) {
+            Some(p) => p.pos,
+            None => return None,
+        };
+        let range = debug::get("pickup_range");
+
+        let mut nearest: Option<(Entity, f32)> = None;
+        for &entity in self.entities() {
+            if !self.is_item(entity) || self.holder(entity).is_some() {
+                continue;
+            }
+            let dist = match self.position(entity) {
+                Some(p) => nalgebra::distance(&pos, &p.pos),
+                None => continue,
+            };
+            if dist <= range && nearest.map_or(true, |(_, d)| dist < d) {
+                nearest = Some((entity, dist));
+            }
+        }
+        nearest.map(|(e, _)| e)
+    }
+
+    /// Picks up the nearest item, if there is one in reach.
+    pub fn pick_up(&mut self, chara: Entity) -> Option<Entity> {
+        let item = self.nearest_item(chara);
+        match item {
+            Some(item) if self.equip(chara, item) => {
+                self.push_event(Event::PickedUp { item: item }, chara);
+                Some(item)
+            },
+            _ => None,
+        }
+    }
+
+    /// Switches `step` weapons forward, or backward if negative.
+    pub fn cycle_weapon(&mut self, chara: Entity, step: i32) {
+        if let Some(inventory) = self.ecs.inventories.get_mut(chara) {
+            inventory.cycle(step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use point::Point;
+
+    #[test]
+    fn test_pick_up_and_drop() {
+        let mut world = World::new(32, 32, 1);
+        let pos = Point::new(16.0, 0.0, 16.0);
+        let chara = world.spawn_prefab("player", pos).unwrap();
+        let first = world.held_gun(chara).unwrap();
+
+        let gun = world.spawn_prefab("smg", pos).unwrap();
+        assert_eq!(world.pick_up(chara), Some(gun));
+        assert_eq!(world.held_gun(chara), Some(gun));
+        assert!(world.is_stowed(first));
+
+        world.cycle_weapon(chara, 1);
+        assert_eq!(world.held_gun(chara), Some(first));
+
+        assert_eq!(world.drop_weapon(chara), Some(first));
+        assert_eq!(world.holder(first), None);
+        assert!(world.held_items(chara) == vec![gun]);
+
+        let wall = world.spawn_prefab("wall", pos).unwrap();
+        assert!(!world.equip(chara, wall));
+        assert!(world.held_items(chara) == vec![gun]);
+
+        world.remove(chara);
+        assert_eq!(world.holder(gun), None);
+        assert!(world.ecs().holds.get(gun).unwrap().0.is_empty());
+    }
+}
@@ -5,27 +5,50 @@ use calx_ecs::Entity;
 use ecs::*;
 use ecs::prefab;
 use ecs::Loadout;
+use ecs::query;
 use ecs::traits::*;
+use ecs::ballistics::Splash;
 use ecs::components::*;
+use lua;
 use point;
 use point::*;
 use world::astar::Grid;
+use world::event::EventBus;
+use world::rng::{RngStream, StreamRng, WorldRng};
 use world::tiles::Tiles;
 
 use ncollide::world::{CollisionGroups, CollisionObject3, CollisionWorld, GeometricQueryType};
 use nalgebra::{self, Isometry3, Point3, Translation3, Vector3, Matrix3x1};
 use ncollide::narrow_phase::{ContactAlgorithm3};
 use ncollide::shape::{Ball, Cylinder, Cuboid, Plane, ShapeHandle3};
-use ncollide::query::{self, Proximity};
+use ncollide::query::{self, Proximity, Ray3};
 use ncollide::events::{ContactEvents};
 use util::translational_ccd_motion_clamping::TranslationalCCDMotionClamping;
 
 pub mod astar;
+pub mod event;
+pub mod faction;
+mod inventory;
 pub mod tiles;
 pub mod gen;
+pub mod rng;
+mod save;
+
+pub use self::event::{Event, EventKind};
+pub use self::save::{SaveError, SaveResult};
 
 pub type CollideWorld = CollisionWorld<Point, Isometry3<f32>, CollisionDataExtra>;
 
+pub const CHARA_HALF_HEIGHT: f32 = 0.5;
+pub const WALL_HALF_HEIGHT: f32 = 10.0;
+pub const BLOCK_HALF_HEIGHT: f32 = 0.25;
+
+/// Where the ground is. Charas standing on it are centered at y = 0.
+pub const FLOOR_HEIGHT: f32 = -CHARA_HALF_HEIGHT;
+
+/// Hits above this fraction of a chara's half height, measured up from its center, hit the head.
+pub const HEAD_ZONE: f32 = 0.5;
+
 #[derive(Clone, Copy, Debug)]
 pub enum CollisionDataExtra {
     Entity(Entity),
@@ -42,8 +65,9 @@ pub struct World {
     pub grid: Grid,
 
     pub tiles: Tiles,
+    rng: WorldRng,
     shapes: HashMap<PhysicsShape, CollisionData>,
-    events: Vec<(Event, Entity)>,
+    events: EventBus,
     kill_list: Vec<Entity>,
 
     // in 32 pixel increments
@@ -61,26 +85,36 @@ fn shape_handles() -> HashMap<PhysicsShape, CollisionData> {
 
     let mut groups = CollisionGroups::new();
     groups.set_membership(&[1]);
-    groups.set_whitelist(&[1, 2, 3]);
+    groups.set_whitelist(&[1, 2, 3, 5]);
     groups.set_blacklist(&[]);
     map.insert(PhysicsShape::Chara, CollisionData {
-        shape: ShapeHandle3::new(Cylinder::new(0.5, 0.5)),
+        shape: ShapeHandle3::new(Cylinder::new(CHARA_HALF_HEIGHT, 0.5)),
         groups: groups,
     });
 
     let mut groups = CollisionGroups::new();
     groups.set_membership(&[2]);
-    groups.set_whitelist(&[1, 3, 4]);
+    groups.set_whitelist(&[1, 3, 4, 5]);
     groups.set_blacklist(&[2]);
     map.insert(PhysicsShape::Wall, CollisionData {
-        shape: ShapeHandle3::new(Cuboid::new(Vector3::new(0.5, 10.0, 0.5))),
+        shape: ShapeHandle3::new(Cuboid::new(Vector3::new(0.5, WALL_HALF_HEIGHT, 0.5))),
+        groups: groups,
+    });
+
+    // Unlike walls, blocks get pushed around, so they have to bump into walls and each other.
+    let mut groups = CollisionGroups::new();
+    groups.set_membership(&[5]);
+    groups.set_whitelist(&[1, 2, 3, 4, 5]);
+    groups.set_blacklist(&[]);
+    map.insert(PhysicsShape::Block, CollisionData {
+        shape: ShapeHandle3::new(Cuboid::new(Vector3::new(0.5, BLOCK_HALF_HEIGHT, 0.5))),
         groups: groups,
     });
 
 
     let mut groups = CollisionGroups::new();
     groups.set_membership(&[3]);
-    groups.set_whitelist(&[1, 2]);
+    groups.set_whitelist(&[1, 2, 5]);
     groups.set_blacklist(&[]);
     map.insert(PhysicsShape::Bullet, CollisionData {
         shape: ShapeHandle3::new(Ball::new(0.5)),
@@ -90,33 +124,47 @@ fn shape_handles() -> HashMap<PhysicsShape, CollisionData> {
     map
 }
 
+/// How far above its bottom a body of the given shape has its center.
+pub fn half_height(shape: PhysicsShape) -> f32 {
+    match shape {
+        PhysicsShape::Chara => CHARA_HALF_HEIGHT,
+        PhysicsShape::Wall => WALL_HALF_HEIGHT,
+        PhysicsShape::Block => BLOCK_HALF_HEIGHT,
+        PhysicsShape::Bullet => 0.5,
+    }
+}
+
 impl World {
-    pub fn new(w: u32, h: u32) -> Self {
+    pub fn new(w: u32, h: u32, seed: u64) -> Self {
         let size = (w, h);
+        let mut world = World::empty(size, Ecs::new(), Tiles::new(size, 0), WorldRng::new(seed));
+
+        let player = world.spawn_prefab("player", Point::new(0.0, 0.0, 0.0)).unwrap();
+        let camera = world.spawn(Loadout::new().c(Camera::new(player)), point::zero()).unwrap();
+
+        world.player = Some(player);
+        world.camera = Some(camera);
+        world
+    }
+
+    fn empty(size: (u32, u32), ecs: Ecs, tiles: Tiles, rng: WorldRng) -> Self {
         let mut collision_world = CollisionWorld::new(0.02);
         let grid = Grid::new(&mut collision_world, size);
         let mut world = World {
-            ecs: Ecs::new(),
+            ecs: ecs,
             player: None,
             camera: None,
             collision_world: collision_world,
             ccd: TranslationalCCDMotionClamping::new(),
             grid: grid,
-            tiles: Tiles::new(size, 0),
+            tiles: tiles,
+            rng: rng,
             shapes: shape_handles(),
-            events: Vec::new(),
+            events: EventBus::new(),
             kill_list: Vec::new(),
             size: size,
         };
-
-        let player = world.spawn(prefab::mob("Dood"), Point::new(0.0, 0.0, 0.0)).unwrap();
-        let camera = world.spawn(Loadout::new().c(Camera::new(player)), point::zero()).unwrap();
-
-        let gun = world.spawn(prefab::gun(), point::zero()).unwrap();
-        world.equip(player, gun);
-
-        world.player = Some(player);
-        world.camera = Some(camera);
+        world.subscribe_defaults();
         world
     }
 
@@ -146,6 +194,86 @@ impl World {
         self.ecs.contains(entity)
     }
 
+    /// Which part of `target` something hit at `height` would hit.
+    pub fn hit_zone(&self, target: Entity, height: f32) -> HitZone {
+        let center = self.position(target).map_or(0.0, |p| p.pos.y);
+        if height - center > CHARA_HALF_HEIGHT * HEAD_ZONE {
+            HitZone::Head
+        } else {
+            HitZone::Body
+        }
+    }
+
+    /// The height `entity`'s center comes to rest at: standing on top of the highest wall or
+    /// block under its center, or on the floor if there is none.
+    pub fn resting_height(&self, entity: Entity) -> f32 {
+        let (pos, shape) = match (self.ecs.positions.get(entity), self.ecs.physics.get(entity)) {
+            (Some(pos), Some(phys)) => (pos.pos, phys.shape),
+            _ => return FLOOR_HEIGHT,
+        };
+
+        // Only what charas can stand on, which is the same as what blocks paths.
+        let mut groups = CollisionGroups::new();
+        groups.set_membership(&[4]);
+        groups.set_whitelist(&[2, 5]);
+
+        let ray = Ray3::new(pos, -Vector3::y());
+        let ground = self.collision_world.interferences_with_ray(&ray, &groups)
+            .filter(|&(obj, _)| match *obj.data() {
+                CollisionDataExtra::Entity(other) => other != entity,
+                CollisionDataExtra::Node => false,
+            })
+            .map(|(_, hit)| hit.toi)
+            .filter(|&toi| toi > 0.0)
+            .fold(FLOOR_HEIGHT, |ground, toi| ground.max(pos.y - toi));
+
+        ground + half_height(shape)
+    }
+
+    /// Hurts everything with health within the splash radius of `pos` that `source` is allowed to
+    /// hurt, walls included, less the further away it is.
+    pub fn splash_damage(&mut self, pos: Point, splash: Splash, source: Option<Entity>) {
+        let hurt: Vec<(Entity, i32)> = query::join2(&self.ecs.healths, &self.ecs.positions)
+            .filter(|&(entity, _, _)| source.map_or(true, |s| self.can_damage(s, entity)))
+            .map(|(entity, _, p)| {
+                let falloff = 1.0 - nalgebra::distance(&pos, &p.pos) / splash.radius;
+                (entity, (splash.damage as f32 * falloff).round() as i32)
+            })
+            .filter(|&(_, amount)| amount > 0)
+            .collect();
+
+        for (entity, amount) in hurt {
+            let event = Event::Damaged {
+                amount: amount,
+                source: source,
+                kind: DamageKind::Explosive,
+                zone: HitZone::Body,
+            };
+            self.push_event(event, entity);
+        }
+    }
+
+    /// The faction the entity belongs to, if it is a chara with a team.
+    pub fn team(&self, entity: Entity) -> Option<&str> {
+        self.ecs.charas.get(entity).and_then(|c| c.team())
+    }
+
+    pub fn are_hostile(&self, a: Entity, b: Entity) -> bool {
+        match (self.team(a), self.team(b)) {
+            (Some(a), Some(b)) => faction::with(|f| f.are_hostile(a, b)),
+            _ => false,
+        }
+    }
+
+    /// Whether `source` is allowed to hurt `target`. Only teammates are off limits, and only
+    /// while friendly fire is off.
+    pub fn can_damage(&self, source: Entity, target: Entity) -> bool {
+        match (self.team(source), self.team(target)) {
+            (Some(a), Some(b)) => faction::with(|f| f.can_damage(a, b)),
+            _ => true,
+        }
+    }
+
     pub fn camera_pos(&self) -> Option<Point> {
         self.camera.map(|c| self.ecs().cameras.get_or_err(c)).and_then(|cam| {
             if !self.contains(cam.following) || !self.ecs().positions.has(cam.following) {
@@ -156,10 +284,24 @@ impl World {
         })
     }
 
+    pub fn camera_pos_interpolated(&self, alpha: f32) -> Option<Point> {
+        self.camera.map(|c| self.ecs().cameras.get_or_err(c)).and_then(|cam| {
+            if !self.contains(cam.following) {
+                None
+            } else {
+                self.ecs().positions.get(cam.following).map(|p| p.interpolated(alpha))
+            }
+        })
+    }
+
     pub fn camera_rot(&self) -> f32 {
         self.camera.map_or(0.0, |c| self.ecs().cameras.get_or_err(c).rot)
     }
 
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
     pub fn in_bounds(&self, pos: &Point) -> bool {
         pos.x >= 0.0 && pos.z >= 0.0 && pos.x < self.size.0 as f32 && pos.z < self.size.1 as f32
     }
@@ -170,6 +312,10 @@ impl World {
         &mut self.ecs
     }
 
+    pub fn rng(&mut self, stream: RngStream) -> &mut StreamRng {
+        self.rng.stream(stream)
+    }
+
     pub fn spawn(&mut self, mut loadout: Loadout, pos: Point) -> Option<Entity> {
         if !self.in_bounds(&pos) {
             return None;
@@ -178,7 +324,42 @@ impl World {
         loadout = loadout.c(Position::new(pos)).c(Holds::new());
 
         let entity = loadout.make(&mut self.ecs);
+        self.add_collision_object(entity);
+
+        Some(entity)
+    }
+
+    /// Spawns a prefab from `data/prefabs` by name, and whatever it says to equip. Bad prefabs are
+    /// logged and spawn nothing.
+    pub fn spawn_prefab(&mut self, name: &str, pos: Point) -> Option<Entity> {
+        let loadout = {
+            let rng = self.rng.stream(RngStream::Appearance);
+            prefab::with(|p| p.loadout(name, rng))
+        };
 
+        let entity = match loadout {
+            Ok(loadout) => self.spawn(loadout, pos),
+            Err(e) => {
+                log!("Can't spawn prefab {}: {:?}", name, e);
+                None
+            },
+        };
+
+        if let Some(entity) = entity {
+            if let Some(item_name) = prefab::equipment(name) {
+                if let Some(item) = self.spawn_prefab(&item_name, pos) {
+                    if !self.equip(entity, item) {
+                        log!("Prefab {} equips {}, which it can't hold", name, item_name);
+                        self.remove(item);
+                    }
+                }
+            }
+        }
+
+        entity
+    }
+
+    fn add_collision_object(&mut self, entity: Entity) {
         if self.ecs.physics.contains(entity) {
             let collision_data = {
                 let phys = self.ecs.physics.get_or_err(entity);
@@ -196,22 +377,38 @@ impl World {
             let mut phys = self.ecs.physics.get_mut_or_err(entity);
             phys.handle = Some(handle);
         }
-
-        Some(entity)
     }
 
     pub fn remove(&mut self, entity: Entity) {
+        self.release_all(entity);
+        lua::world::release(entity);
+
         if self.ecs.physics.contains(entity) {
-            let handle = self.ecs.physics.get_mut_or_err(entity).handle;
+            let (handle, shape) = {
+                let phys = self.ecs.physics.get_or_err(entity);
+                (phys.handle, phys.shape)
+            };
 
             if let Some(handle) = handle {
                 self.collision_world.remove(&[handle]);
+
+                // A destroyed wall opens up a path right away, not at the next grid rebuild.
+                if shape == PhysicsShape::Wall || shape == PhysicsShape::Block {
+                    if let Some(pos) = self.ecs.positions.get(entity).map(|p| p.pos) {
+                        self.grid.discretize_around(&self.collision_world, pos, 1.0);
+                    }
+                }
             }
         }
 
         self.ecs.remove(entity);
     }
 
+    /// Remembers where everything is before a tick moves it.
+    pub fn store_last_positions(&mut self) {
+        query::each_mut(&mut self.ecs.positions, |_, pos| pos.last_pos = pos.pos);
+    }
+
     pub fn kill(&mut self, entity: Entity) {
         self.ecs_mut().healths.map_mut(|h| h.kill(), entity);
     }
@@ -222,74 +419,62 @@ impl World {
         }
     }
 
-    pub fn equip(&mut self, chara: Entity, gun: Entity) {
-        {
-            let mut holds = self.ecs.holds.get_mut_or_err(chara);
-            holds.0.insert(gun, true);
-        }
-        {
-            let mut holds = self.ecs.holds.get_mut_or_err(gun);
-            holds.0.insert(chara, false);
-        }
-    }
-
     pub fn update_physics(&mut self, remake_grid: bool) {
+        profile_scope!("update_physics");
         self.update_world_to_physics();
         self.update_collision_world();
         self.update_physics_to_world(remake_grid);
     }
 
     fn update_world_to_physics(&mut self) {
-        let mut entities = Vec::new();
-        for entity in self.entities() {
-            if self.ecs.physics.has(*entity) {
-                entities.push(*entity);
-            }
-        }
-
-        for entity in entities.iter() {
-            {
-                let pos = self.ecs.positions.get_or_err(*entity);
-                let handle = self.ecs.physics.get_or_err(*entity).handle;
-                if let Some(handle) = handle {
-                    //println!("{:?}", pos.translation.vector);
-                    if self.collision_world.collision_object(handle).is_none() {
-                        // This should happen exactly once for each object when it is first created.
-                        // `CreateObjectSys` has added the object, but the collision world has
-                        // not been updated yet, so changing the position here would be an error.
-                        continue;
-                    }
-                    let pos = Isometry3::new(Vector3::new(pos.pos.x, pos.pos.y, pos.pos.z), nalgebra::zero());
-                    self.collision_world.set_position(handle, pos);
+        profile_scope!("world_to_physics");
+        for (_, phys, pos) in query::join2(&self.ecs.physics, &self.ecs.positions) {
+            if let Some(handle) = phys.handle {
+                if self.collision_world.collision_object(handle).is_none() {
+                    // This should happen exactly once for each object when it is first created.
+                    // `CreateObjectSys` has added the object, but the collision world has
+                    // not been updated yet, so changing the position here would be an error.
+                    continue;
                 }
+                let pos = Isometry3::new(Vector3::new(pos.pos.x, pos.pos.y, pos.pos.z), nalgebra::zero());
+                self.collision_world.set_position(handle, pos);
             }
         }
-
     }
 
     fn update_collision_world(&mut self) {
-        self.collision_world.perform_broad_phase();
-        if !self.ccd.update(&mut self.collision_world) {
-            self.collision_world.perform_narrow_phase();
+        {
+            profile_scope!("broad_phase");
+            self.collision_world.perform_broad_phase();
+        }
+        {
+            profile_scope!("narrow_phase");
+            if !self.ccd.update(&mut self.collision_world) {
+                self.collision_world.perform_narrow_phase();
+            }
         }
         self.collision_world.update();
     }
 
     fn update_physics_to_world(&mut self, remake_grid: bool) {
+        profile_scope!("physics_to_world");
         let mut vec = Vec::new();
         for (e1, e2, ca) in self.collision_world.contact_pairs() {
             let mut contacts = Vec::new();
             ca.contacts(&mut contacts);
             for contact in contacts {
-                let move_vec = contact.normal.unwrap() * contact.depth * -0.5;
-                let move_vec_b = move_vec * -1.0;
-                vec.push((*e1.data(), *e2.data(), move_vec, move_vec_b));
+                vec.push((*e1.data(), *e2.data(), contact.normal.unwrap(), contact.depth));
             }
         }
 
-        for (a, b, m1, m2) in vec {
-            self.collide_two(a, b, &m1);
-            self.collide_two(b, a, &m2);
+        for (a, b, normal, depth) in vec {
+            let move_vec = normal * depth * -0.5;
+            self.collide_two(a, b, &move_vec);
+            self.collide_two(b, a, &(move_vec * -1.0));
+
+            if let (CollisionDataExtra::Entity(a), CollisionDataExtra::Entity(b)) = (a, b) {
+                self.separate_bodies(a, b, &normal, depth);
+            }
         }
 
         // recalculating astar grid is expensive, only try once in a while.
@@ -299,6 +484,7 @@ impl World {
     }
 
     fn discretize_grid(&mut self) {
+        profile_scope!("discretize_grid");
         self.grid.discretize(&self.collision_world);
     }
 
@@ -310,57 +496,151 @@ impl World {
         }
     }
 
-    fn collide_two_entities(&mut self, a: Entity, b: Entity, move_vec: &Matrix3x1<f32>) {
-        if !self.ecs.bullets.has(a) && !self.ecs.bullets.has(b) && self.ecs.charas.has(a) {
-            if let Some(pos) = self.ecs.positions.get_mut(a) {
-                pos.pos.x += move_vec.x;
-                pos.pos.z += move_vec.z;
+    /// Pushes two overlapping bodies apart and takes away their speed into each other, so they
+    /// slide along instead, bouncing back by the bouncier one's restitution. The lighter body
+    /// gives way more, so momentum is kept, and static bodies don't give way at all. Only
+    /// sideways, since heights are settled by standing and falling.
+    fn separate_bodies(&mut self, a: Entity, b: Entity, normal: &Vector3<f32>, depth: f32) {
+        let (inv_a, inv_b, vel_a, vel_b, restitution) = {
+            let (pa, pb) = match (self.ecs.physics.get(a), self.ecs.physics.get(b)) {
+                (Some(pa), Some(pb)) => (pa, pb),
+                _ => return,
+            };
+            if pa.kind == PhysicsKind::Bullet || pb.kind == PhysicsKind::Bullet {
+                return;
             }
+            (pa.inverse_mass(), pb.inverse_mass(), pa.vel, pb.vel, pa.restitution.max(pb.restitution))
+        };
+
+        let total = inv_a + inv_b;
+        let normal = Vector3::new(normal.x, 0.0, normal.z);
+        let len = normal.norm();
+        if total == 0.0 || len < 0.001 {
+            return;
+        }
+
+        // Points from `a` to `b`.
+        let normal = normal / len;
+        let push = normal * depth.max(0.0) / total;
+        let closing = (vel_a - vel_b).dot(&normal);
+        let impulse = if closing > 0.0 {
+            normal * (1.0 + restitution) * closing / total
+        } else {
+            Vector3::new(0.0, 0.0, 0.0)
+        };
+
+        if let Some(pos) = self.ecs.positions.get_mut(a) {
+            pos.pos -= push * inv_a;
+        }
+        if let Some(pos) = self.ecs.positions.get_mut(b) {
+            pos.pos += push * inv_b;
         }
+        self.ecs.physics.get_mut_or_err(a).vel -= impulse * inv_a;
+        self.ecs.physics.get_mut_or_err(b).vel += impulse * inv_b;
+    }
 
+    fn collide_two_entities(&mut self, a: Entity, b: Entity, move_vec: &Matrix3x1<f32>) {
         if self.ecs().bullets.has(a) {
+            let (fired_by, damage, kind, knockback, splash) = {
+                let bullet = self.ecs().bullets.get_or_err(a);
+                if bullet.hits.contains(&b) {
+                    return;
+                }
+                (bullet.fired_by, bullet.damage, bullet.kind, bullet.knockback, bullet.splash)
+            };
+
+            let mut stopped = true;
             if self.ecs().charas.has(b) {
-                // TODO: blacklist bullet from other team
-                let fired_by = self.ecs().bullets.get_or_err(a).fired_by;
-                if fired_by == b {
+                if fired_by == b || !self.can_damage(fired_by, b) {
                     return;
                 }
 
-                let damage = self.ecs().bullets.get_or_err(a).damage;
-                self.push_event(Event::Hurt(damage), b);
+                let height = self.ecs().positions.get_or_err(a).pos.y;
+                let event = Event::Damaged {
+                    amount: damage,
+                    source: Some(fired_by),
+                    kind: kind,
+                    zone: self.hit_zone(b, height),
+                };
+                self.push_event(event, b);
+                let bullet = self.ecs.bullets.get_mut_or_err(a);
+                if bullet.penetration > 0 {
+                    bullet.penetration -= 1;
+                    stopped = false;
+                }
+            } else if self.ecs().healths.has(b) {
+                let event = Event::Damaged {
+                    amount: damage,
+                    source: Some(fired_by),
+                    kind: kind,
+                    zone: HitZone::Body,
+                };
+                self.push_event(event, b);
+            }
+            self.ecs.bullets.get_mut_or_err(a).hits.push(b);
+            self.push_event(Event::Collide(*move_vec * knockback), b);
+
+            if stopped {
+                if let Some(splash) = splash {
+                    let pos = self.ecs().positions.get_or_err(a).pos;
+                    self.splash_damage(pos, splash, Some(fired_by));
+                    self.push_event(Event::Explosion { pos: pos, radius: splash.radius }, a);
+                }
+                self.push_event(Event::Destroy, a);
             }
-            self.push_event(Event::Collide(move_vec.clone()), b);
-            self.push_event(Event::Destroy, a);
         }
     }
+}
 
-    pub fn push_event(&mut self, event: Event, entity: Entity) {
-        self.events.push((event, entity));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::ballistics;
+
+    fn fire(world: &mut World, kind: &str, pos: Point) -> Entity {
+        let def = ballistics::def(kind).unwrap();
+        let player = world.player().unwrap();
+        world.spawn(prefab::bullet(&def, player), pos).unwrap()
     }
 
-    pub fn handle_events(&mut self) {
-        while let Some((event, entity)) = self.events.pop() {
-            match event {
-                Event::Hurt(damage) => {
-                    if let Some(health) = self.ecs_mut().healths.get_mut(entity) {
-                        health.hurt(damage);
-                    }
-                },
-                Event::Destroy => {
-                    self.kill_list.push(entity);
-                },
-                Event::Collide(vec) => {
-                    if let Some(phys) = self.ecs_mut().physics.get_mut(entity) {
-                        phys.vel += Vector3::new(-vec.x, 0.0, -vec.z);
-                    }
-                }
-            }
-        }
+    fn hit_points(world: &World, entity: Entity) -> i32 {
+        world.ecs().healths.get_or_err(entity).hit_points
     }
-}
 
-pub enum Event {
-    Hurt(i32),
-    Destroy,
-    Collide(Matrix3x1<f32>),
+    #[test]
+    fn test_piercing_passes_through() {
+        let mut world = World::new(32, 32, 1);
+        let first = world.spawn_prefab("dood", Point::new(4.0, 0.0, 4.0)).unwrap();
+        let second = world.spawn_prefab("dood", Point::new(6.0, 0.0, 4.0)).unwrap();
+        let bullet = fire(&mut world, "Piercing", Point::new(4.0, 0.0, 4.0));
+        let push = Matrix3x1::new(0.0, 0.0, 0.0);
+
+        world.collide_two_entities(bullet, first, &push);
+        world.collide_two_entities(bullet, second, &push);
+        // Still overlapping the first one doesn't hit it again.
+        world.collide_two_entities(bullet, first, &push);
+        world.handle_events();
+        world.purge_dead();
+
+        let damage = ballistics::def("Piercing").unwrap().damage;
+        assert_eq!(hit_points(&world, first), 1000 - damage);
+        assert_eq!(hit_points(&world, second), 1000 - damage);
+        assert!(world.contains(bullet));
+    }
+
+    #[test]
+    fn test_rocket_explodes() {
+        let mut world = World::new(32, 32, 1);
+        let dood = world.spawn_prefab("dood", Point::new(4.0, 0.0, 4.0)).unwrap();
+        let rocket = fire(&mut world, "Rocket", Point::new(4.0, 0.0, 4.0));
+
+        world.collide_two_entities(rocket, dood, &Matrix3x1::new(0.0, 0.0, 0.0));
+        world.handle_events();
+        world.purge_dead();
+
+        // Hit directly, and by the splash on top of that.
+        let damage = ballistics::def("Rocket").unwrap().damage;
+        assert!(hit_points(&world, dood) < 1000 - damage);
+        assert!(!world.contains(rocket));
+    }
 }
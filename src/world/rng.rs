@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+/// Each subsystem draws from its own stream, so adding a random call to one of them doesn't
+/// change the results of the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RngStream {
+    Session,
+    Gen,
+    Spawn,
+    Appearance,
+    Ai,
+    Combat,
+    Script,
+}
+
+impl RngStream {
+    fn salt(&self) -> u64 {
+        match *self {
+            RngStream::Session    => 1,
+            RngStream::Gen        => 2,
+            RngStream::Spawn      => 3,
+            RngStream::Appearance => 4,
+            RngStream::Ai         => 5,
+            RngStream::Combat     => 6,
+            RngStream::Script     => 7,
+        }
+    }
+}
+
+/// The xorshift128 generator `XorShiftRng` uses, but with its state out in the open, so saves can
+/// store it and the loaded world carries on with exactly the same random results.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamRng {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+}
+
+impl StreamRng {
+    fn from_seed(seed: [u32; 4]) -> Self {
+        assert!(seed != [0, 0, 0, 0], "xorshift can't be seeded with all zeroes");
+        StreamRng { x: seed[0], y: seed[1], z: seed[2], w: seed[3] }
+    }
+}
+
+impl Rng for StreamRng {
+    fn next_u32(&mut self) -> u32 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ (t ^ (t >> 8));
+        self.w
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldRng {
+    seed: u64,
+    streams: HashMap<RngStream, StreamRng>,
+}
+
+impl WorldRng {
+    pub fn new(seed: u64) -> Self {
+        WorldRng {
+            seed: seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StreamRng {
+        let seed = self.seed;
+        self.streams.entry(stream).or_insert_with(|| make_rng(seed, stream.salt()))
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn make_rng(seed: u64, salt: u64) -> StreamRng {
+    let mut state = seed ^ salt.wrapping_mul(0xD6E8FEB86659FD93);
+    let a = splitmix64(&mut state);
+    let b = splitmix64(&mut state);
+
+    // xorshift never leaves an all-zero state.
+    let seed = [a as u32, (a >> 32) as u32, b as u32, ((b >> 32) as u32) | 1];
+    StreamRng::from_seed(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode;
+    use rand::{SeedableRng, XorShiftRng};
+
+    #[test]
+    fn test_same_seed() {
+        let mut a = WorldRng::new(1234);
+        let mut b = WorldRng::new(1234);
+        for _ in 0..100 {
+            assert_eq!(a.stream(RngStream::Gen).next_u32(), b.stream(RngStream::Gen).next_u32());
+        }
+    }
+
+    #[test]
+    fn test_streams_independent() {
+        let mut a = WorldRng::new(1234);
+        let mut b = WorldRng::new(1234);
+        a.stream(RngStream::Combat).next_u32();
+        assert_eq!(a.stream(RngStream::Gen).next_u32(), b.stream(RngStream::Gen).next_u32());
+        assert!(a.stream(RngStream::Ai).next_u32() != b.stream(RngStream::Spawn).next_u32());
+    }
+
+    #[test]
+    fn test_same_as_xorshift() {
+        let seed = [1, 2, 3, 4];
+        let mut ours = StreamRng::from_seed(seed);
+        let mut theirs = XorShiftRng::from_seed(seed);
+        for _ in 0..100 {
+            assert_eq!(ours.next_u32(), theirs.next_u32());
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut a = WorldRng::new(1234);
+        a.stream(RngStream::Combat).next_u32();
+
+        let bytes = bincode::serialize(&a, bincode::Infinite).unwrap();
+        let mut b: WorldRng = bincode::deserialize(&bytes).unwrap();
+        for _ in 0..100 {
+            assert_eq!(a.stream(RngStream::Combat).next_u32(), b.stream(RngStream::Combat).next_u32());
+        }
+        assert_eq!(a.stream(RngStream::Gen).next_u32(), b.stream(RngStream::Gen).next_u32());
+    }
+}
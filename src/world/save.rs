@@ -0,0 +1,138 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use bincode;
+use calx_ecs::Entity;
+
+use ecs::Ecs;
+use super::World;
+use super::rng::WorldRng;
+use super::tiles::Tiles;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Bincode(bincode::Error),
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> SaveError {
+        SaveError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SaveError {
+    fn from(e: bincode::Error) -> SaveError {
+        SaveError::Bincode(e)
+    }
+}
+
+pub type SaveResult<T> = Result<T, SaveError>;
+
+/// The parts of a `World` that are written to disk. The collision world, CCD entries and A* grid
+/// are rebuilt from the ECS on load.
+#[derive(Serialize)]
+struct WorldSaveRef<'a> {
+    ecs: &'a Ecs,
+    player: Option<Entity>,
+    camera: Option<Entity>,
+    tiles: &'a Tiles,
+    size: (u32, u32),
+    rng: &'a WorldRng,
+}
+
+#[derive(Deserialize)]
+struct WorldSave {
+    ecs: Ecs,
+    player: Option<Entity>,
+    camera: Option<Entity>,
+    tiles: Tiles,
+    size: (u32, u32),
+    rng: WorldRng,
+}
+
+impl World {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> SaveResult<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let save = WorldSaveRef {
+            ecs: &self.ecs,
+            player: self.player,
+            camera: self.camera,
+            tiles: &self.tiles,
+            size: self.size,
+            rng: &self.rng,
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, &save, bincode::Infinite)?;
+        Ok(())
+    }
+
+    /// Event subscribers can't be saved, so the loaded world has the same default ones a new world
+    /// starts with.
+    pub fn load<P: AsRef<Path>>(path: P) -> SaveResult<World> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let save: WorldSave = bincode::deserialize_from(&mut reader, bincode::Infinite)?;
+
+        let mut world = World::empty(save.size, save.ecs, save.tiles, save.rng);
+        world.player = save.player;
+        world.camera = save.camera;
+
+        let entities: Vec<Entity> = world.entities().cloned().collect();
+        for entity in entities {
+            world.add_collision_object(entity);
+        }
+
+        world.update_collision_world();
+        world.discretize_grid();
+
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+    use ecs::traits::*;
+    use rand::Rng;
+    use world::EventKind;
+    use world::rng::RngStream;
+
+    #[test]
+    fn test_round_trip() {
+        let path = env::temp_dir().join("potato-dan-test.sav");
+        let mut world = World::new(32, 32, 1);
+        world.save(&path).unwrap();
+
+        let loaded = World::load(&path).unwrap();
+        assert_eq!(world.entities().len(), loaded.entities().len());
+        assert_eq!(world.player(), loaded.player());
+        assert_eq!(world.events.subscribers(EventKind::Killed).len(),
+                   loaded.events.subscribers(EventKind::Killed).len());
+        for entity in loaded.entities() {
+            if let Some(phys) = loaded.ecs().physics.get(*entity) {
+                assert!(phys.handle.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_keeps_random_results() {
+        let path = env::temp_dir().join("potato-dan-test-rng.sav");
+        let mut saved = World::new(32, 32, 1);
+        let mut unsaved = World::new(32, 32, 1);
+        saved.save(&path).unwrap();
+        let mut loaded = World::load(&path).unwrap();
+
+        for _ in 0..10 {
+            let expected = unsaved.rng(RngStream::Combat).next_u32();
+            assert_eq!(saved.rng(RngStream::Combat).next_u32(), expected);
+            assert_eq!(loaded.rng(RngStream::Combat).next_u32(), expected);
+        }
+    }
+}
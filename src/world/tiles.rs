@@ -1,5 +1,6 @@
 use point::Point2d;
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Tiles {
     size: (u32, u32),
     tiles: Vec<u32>,